@@ -0,0 +1,61 @@
+// Convert days since the Unix epoch to a (year, month, day) civil date
+pub fn civil_from_days(days: u64) -> (u32, u32, u32) {
+    // Shift the epoch to 0000-03-01 so leap days fall at the end of the year
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = (yoe + era * 400) as u32 + u32::from(month <= 2);
+    (year, month, day)
+}
+
+// Convert a (year, month, day) civil date to days since the Unix epoch
+pub fn days_from_civil(year: u32, month: u32, day: u32) -> u64 {
+    // Same March-based year as civil_from_days
+    let year = u64::from(year) - u64::from(month <= 2);
+    let era = year / 400;
+    let yoe = year % 400;
+    let mp = u64::from((month + 9) % 12);
+    let doy = (153 * mp + 2) / 5 + u64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    (era * 146_097 + doe).saturating_sub(719_468)
+}
+
+// Check whether the given day is on a list of (year, month, day) dates,
+// where year 0 matches every year
+pub fn is_skip_date(dates: &[(u32, u32, u32)], day: u64) -> bool {
+    let (year, month, mday) = civil_from_days(day);
+    dates
+        .iter()
+        .any(|&(y, m, d)| (y == 0 || y == year) && m == month && d == mday)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_dates_round_trip() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+        for days in [0, 59, 365, 11_016, 19_782, 20_000, 47_541] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn skip_dates_match_any_year_with_year_zero() {
+        let dates = [(0, 1, 1), (2024, 12, 25)];
+        assert!(is_skip_date(&dates, days_from_civil(2024, 1, 1)));
+        assert!(is_skip_date(&dates, days_from_civil(2031, 1, 1)));
+        assert!(is_skip_date(&dates, days_from_civil(2024, 12, 25)));
+        assert!(!is_skip_date(&dates, days_from_civil(2025, 12, 25)));
+        assert!(!is_skip_date(&dates, days_from_civil(2024, 1, 2)));
+    }
+}
@@ -0,0 +1,93 @@
+// How many beeps the hourly chime gives at midnight
+#[derive(Clone, Copy, Debug)]
+pub enum MidnightChime {
+    Silent,
+    Twelve,
+    TwentyFour, // Still limited by the repeat cap
+}
+
+// Number of beeps for the hourly chime, switching to 12-hour counting above
+// `max_repeats`. Midnight and noon follow the given presets, 0 means no chime.
+pub fn hour_chime_repeats(
+    hour: u8,
+    midnight: MidnightChime,
+    noon_enabled: bool,
+    max_repeats: u8,
+) -> u8 {
+    let hour = match hour {
+        0 => match midnight {
+            MidnightChime::Silent => return 0,
+            MidnightChime::Twelve => 12,
+            MidnightChime::TwentyFour => 24,
+        },
+        12 if !noon_enabled => return 0,
+        h => h,
+    };
+
+    if hour <= max_repeats {
+        return hour;
+    }
+
+    let twelve_hour = match hour % 12 {
+        0 => 12,
+        h => h,
+    };
+    twelve_hour.min(max_repeats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The firmware defaults: twelve at midnight, noon enabled, capped at 12
+    fn repeats(hour: u8) -> u8 {
+        hour_chime_repeats(hour, MidnightChime::Twelve, true, 12)
+    }
+
+    #[test]
+    fn counts_in_twelve_hour_time() {
+        assert_eq!(repeats(0), 12);
+        assert_eq!(repeats(1), 1);
+        assert_eq!(repeats(11), 11);
+        assert_eq!(repeats(12), 12);
+        assert_eq!(repeats(13), 1);
+        assert_eq!(repeats(23), 11);
+    }
+
+    #[test]
+    fn midnight_presets() {
+        assert_eq!(hour_chime_repeats(0, MidnightChime::Silent, true, 12), 0);
+        assert_eq!(hour_chime_repeats(0, MidnightChime::Twelve, true, 12), 12);
+        assert_eq!(
+            hour_chime_repeats(0, MidnightChime::TwentyFour, true, 24),
+            24
+        );
+        // Twenty-four beeps are still held to the cap
+        assert_eq!(
+            hour_chime_repeats(0, MidnightChime::TwentyFour, true, 12),
+            12
+        );
+    }
+
+    #[test]
+    fn noon_disabled_is_silent() {
+        assert_eq!(hour_chime_repeats(12, MidnightChime::Twelve, false, 12), 0);
+        // Only noon itself; midnight and 13:00 still chime
+        assert_eq!(hour_chime_repeats(0, MidnightChime::Twelve, false, 12), 12);
+        assert_eq!(hour_chime_repeats(13, MidnightChime::Twelve, false, 12), 1);
+    }
+
+    #[test]
+    fn cap_limits_the_count() {
+        // A 24-hour cap counts the afternoon hours in full
+        assert_eq!(hour_chime_repeats(13, MidnightChime::Twelve, true, 24), 13);
+        assert_eq!(hour_chime_repeats(23, MidnightChime::Twelve, true, 24), 23);
+
+        // A low cap holds every hour to it
+        for hour in 0..24 {
+            assert!(hour_chime_repeats(hour, MidnightChime::Twelve, true, 3) <= 3);
+        }
+        assert_eq!(hour_chime_repeats(2, MidnightChime::Twelve, true, 3), 2);
+        assert_eq!(hour_chime_repeats(9, MidnightChime::Twelve, true, 3), 3);
+    }
+}
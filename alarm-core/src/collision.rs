@@ -0,0 +1,104 @@
+use crate::schedule::AlarmType;
+use std::cmp::Reverse;
+
+// What to do when several alarms come due in the same minute
+#[derive(Clone, Copy, Debug)]
+pub enum CollisionPolicy {
+    PlayAll,         // Play each one in turn
+    HighestPriority, // Play only the highest-priority alarm, the earliest on a tie
+    FirstOnly,       // Play only the first alarm in schedule order
+}
+
+// Apply the collision policy to the alarms that came due in the same minute,
+// given in schedule order. Returns the alarms to play and, still in schedule
+// order, the ones skipped in their favour.
+pub fn resolve_collisions<T>(
+    policy: CollisionPolicy,
+    mut fired: Vec<T>,
+    alarm_type: impl Fn(&T) -> AlarmType,
+) -> (Vec<T>, Vec<T>) {
+    if fired.len() < 2 {
+        return (fired, Vec::new());
+    }
+
+    let keep = match policy {
+        CollisionPolicy::PlayAll => return (fired, Vec::new()),
+        CollisionPolicy::HighestPriority => fired
+            .iter()
+            .enumerate()
+            .max_by_key(|(i, alarm)| (alarm_type(alarm).priority(), Reverse(*i)))
+            .map_or(0, |(i, _)| i),
+        CollisionPolicy::FirstOnly => 0,
+    };
+
+    let kept = fired.remove(keep);
+    (vec![kept], fired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use AlarmType::*;
+
+    fn resolve(policy: CollisionPolicy, fired: &[AlarmType]) -> (Vec<AlarmType>, Vec<AlarmType>) {
+        resolve_collisions(policy, fired.to_vec(), |&alarm| alarm)
+    }
+
+    #[test]
+    fn play_all_keeps_everything_in_order() {
+        let fired = [QuarterChime, HourChime, Bedtime];
+        assert_eq!(
+            resolve(CollisionPolicy::PlayAll, &fired),
+            (fired.to_vec(), vec![])
+        );
+    }
+
+    #[test]
+    fn highest_priority_keeps_one() {
+        assert_eq!(
+            resolve(CollisionPolicy::HighestPriority, &[QuarterChime, HourChime]),
+            (vec![HourChime], vec![QuarterChime])
+        );
+        assert_eq!(
+            resolve(
+                CollisionPolicy::HighestPriority,
+                &[QuarterChime, Reminder, Bedtime]
+            ),
+            (vec![Bedtime], vec![QuarterChime, Reminder])
+        );
+    }
+
+    #[test]
+    fn highest_priority_tie_goes_to_the_earliest() {
+        let (kept, skipped) = resolve_collisions(
+            CollisionPolicy::HighestPriority,
+            vec![(Reminder, 'a'), (HourChime, 'b'), (HourChime, 'c')],
+            |&(alarm, _)| alarm,
+        );
+        assert_eq!(kept, [(HourChime, 'b')]);
+        assert_eq!(skipped, [(Reminder, 'a'), (HourChime, 'c')]);
+    }
+
+    #[test]
+    fn first_only_keeps_the_first() {
+        assert_eq!(
+            resolve(
+                CollisionPolicy::FirstOnly,
+                &[QuarterChime, HourChime, Bedtime]
+            ),
+            (vec![QuarterChime], vec![HourChime, Bedtime])
+        );
+    }
+
+    #[test]
+    fn a_single_alarm_never_collides() {
+        for policy in [
+            CollisionPolicy::PlayAll,
+            CollisionPolicy::HighestPriority,
+            CollisionPolicy::FirstOnly,
+        ] {
+            assert_eq!(resolve(policy, &[Reminder]), (vec![Reminder], vec![]));
+            assert_eq!(resolve(policy, &[]), (vec![], vec![]));
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// Whether a trigger due at `due_minute` should fire at `now_minute`, both in
+// minutes since the epoch. Each due time fires once, even when the loop only
+// gets to it after its minute has passed. After a reboot the map is empty,
+// so anything already due fires again.
+pub fn should_fire<K: Eq + Hash>(
+    trigger: K,
+    due_minute: u64,
+    now_minute: u64,
+    last_fired: &HashMap<K, u64>,
+) -> bool {
+    now_minute >= due_minute && !already_fired(trigger, due_minute, last_fired)
+}
+
+// Like should_fire, but also records the due time as handled when it returns
+// true, so a suppressed alarm is reported once rather than on every pass
+pub fn claim_due<K: Eq + Hash + Copy>(
+    trigger: K,
+    due_minute: u64,
+    now_minute: u64,
+    last_fired: &mut HashMap<K, u64>,
+) -> bool {
+    if !should_fire(trigger, due_minute, now_minute, last_fired) {
+        return false;
+    }
+
+    last_fired.insert(trigger, due_minute);
+    true
+}
+
+// Whether the trigger has already fired for this due time, or a later one
+pub fn already_fired<K: Eq + Hash>(
+    trigger: K,
+    due_minute: u64,
+    last_fired: &HashMap<K, u64>,
+) -> bool {
+    last_fired
+        .get(&trigger)
+        .is_some_and(|&last| last >= due_minute)
+}
+
+// Record every due time already reached as fired, leaving later ones alone.
+// Used after a planned restart, so alarms that sounded before it don't sound
+// again when the empty map would otherwise let them through.
+pub fn seed_fired<K: Eq + Hash + Copy>(
+    due: impl IntoIterator<Item = (K, u64)>,
+    now_minute: u64,
+    last_fired: &mut HashMap<K, u64>,
+) {
+    for (trigger, due_minute) in due {
+        if should_fire(trigger, due_minute, now_minute, last_fired) {
+            last_fired.insert(trigger, due_minute);
+        }
+    }
+}
+
+// What to do when the clock steps backwards, e.g. after a large NTP correction
+#[derive(Clone, Copy, Debug)]
+pub enum BackwardJumpPolicy {
+    SkipRewound, // Keep the fired-alarm history so rewound alarms don't sound twice
+    Refire,      // Forget the history so alarms in the rewound window sound again
+}
+
+// Apply the policy if the clock has stepped back by more than `threshold_secs`
+// between two readings, returning the size of the step. Smaller steps are
+// ignored.
+pub fn handle_backward_jump<K>(
+    previous_secs: u64,
+    now_secs: u64,
+    threshold_secs: u64,
+    policy: BackwardJumpPolicy,
+    last_fired: &mut HashMap<K, u64>,
+) -> Option<u64> {
+    if previous_secs <= now_secs + threshold_secs {
+        return None;
+    }
+
+    if let BackwardJumpPolicy::Refire = policy {
+        last_fired.clear();
+    }
+    Some(previous_secs - now_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::{AlarmType, Trigger};
+
+    const DUE: u64 = 28_000_000; // Some minute since the epoch
+
+    #[test]
+    fn fires_once_in_its_minute() {
+        let mut last_fired = HashMap::new();
+        assert!(claim_due("chime", DUE, DUE, &mut last_fired));
+
+        // The loop runs several times a minute; later passes must not refire
+        assert!(!should_fire("chime", DUE, DUE, &last_fired));
+        assert!(!claim_due("chime", DUE, DUE, &mut last_fired));
+        assert!(!claim_due("chime", DUE, DUE + 1, &mut last_fired));
+    }
+
+    #[test]
+    fn waits_for_its_minute() {
+        let mut last_fired = HashMap::new();
+        assert!(!claim_due("chime", DUE, DUE - 1, &mut last_fired));
+        assert!(last_fired.is_empty());
+        assert!(claim_due("chime", DUE, DUE, &mut last_fired));
+    }
+
+    #[test]
+    fn late_pass_still_fires() {
+        // A loop iteration held up past the due minute fires on arrival, once
+        let mut last_fired = HashMap::new();
+        assert!(claim_due("chime", DUE, DUE + 3, &mut last_fired));
+        assert!(!claim_due("chime", DUE, DUE + 4, &mut last_fired));
+        assert_eq!(last_fired.get("chime"), Some(&DUE));
+    }
+
+    #[test]
+    fn next_due_time_fires_again() {
+        let mut last_fired = HashMap::new();
+        assert!(claim_due("chime", DUE, DUE, &mut last_fired));
+        assert!(claim_due("chime", DUE + 60, DUE + 60, &mut last_fired));
+    }
+
+    #[test]
+    fn triggers_are_tracked_separately() {
+        let mut last_fired = HashMap::new();
+        assert!(claim_due("chime", DUE, DUE, &mut last_fired));
+        assert!(claim_due("reminder", DUE, DUE, &mut last_fired));
+        assert!(already_fired("chime", DUE, &last_fired));
+        assert!(!already_fired("bedtime", DUE, &last_fired));
+    }
+
+    #[test]
+    fn empty_map_after_reboot_fires_what_is_due() {
+        // Nothing survives a reboot, so a due time already reached fires again
+        let last_fired: HashMap<&str, u64> = HashMap::new();
+        assert!(!already_fired("chime", DUE, &last_fired));
+        assert!(should_fire("chime", DUE, DUE, &last_fired));
+        assert!(should_fire("chime", DUE, DUE + 30, &last_fired));
+        assert!(!should_fire("chime", DUE + 1, DUE, &last_fired));
+    }
+
+    #[test]
+    fn seeded_map_after_planned_restart_skips_what_is_due() {
+        let mut last_fired = HashMap::new();
+        seed_fired(
+            [("chime", DUE), ("reminder", DUE + 10)],
+            DUE + 1,
+            &mut last_fired,
+        );
+
+        // The chime sounded before the restart; the reminder is still ahead
+        assert!(!claim_due("chime", DUE, DUE + 1, &mut last_fired));
+        assert!(!claim_due("reminder", DUE + 10, DUE + 9, &mut last_fired));
+        assert!(claim_due("reminder", DUE + 10, DUE + 10, &mut last_fired));
+        assert!(claim_due("chime", DUE + 60, DUE + 60, &mut last_fired));
+    }
+
+    // Fire the hour chime due at DUE, then step the clock back five minutes
+    // and run it forward through DUE again, counting how often it fires
+    fn fires_across_backward_jump(policy: BackwardJumpPolicy) -> usize {
+        let trigger = Trigger::Alarm(AlarmType::HourChime);
+        let mut last_fired = HashMap::new();
+        let mut fired = 0;
+
+        let mut previous = None;
+        let readings = (DUE * 60..DUE * 60 + 120).chain(DUE * 60 - 300..DUE * 60 + 120);
+        for now in readings.step_by(10) {
+            if let Some(previous) = previous {
+                handle_backward_jump(previous, now, 60, policy, &mut last_fired);
+            }
+            previous = Some(now);
+
+            if claim_due(trigger, DUE, now / 60, &mut last_fired) {
+                fired += 1;
+            }
+        }
+        fired
+    }
+
+    #[test]
+    fn skip_rewound_does_not_refire() {
+        assert_eq!(
+            fires_across_backward_jump(BackwardJumpPolicy::SkipRewound),
+            1
+        );
+    }
+
+    #[test]
+    fn refire_sounds_rewound_alarms_again() {
+        assert_eq!(fires_across_backward_jump(BackwardJumpPolicy::Refire), 2);
+    }
+
+    #[test]
+    fn reports_only_large_backward_steps() {
+        let mut last_fired = HashMap::from([("chime", DUE)]);
+        let policy = BackwardJumpPolicy::Refire;
+        assert_eq!(
+            handle_backward_jump(1000, 1060, 60, policy, &mut last_fired),
+            None
+        );
+        assert_eq!(
+            handle_backward_jump(1000, 940, 60, policy, &mut last_fired),
+            None
+        );
+        assert_eq!(last_fired.len(), 1);
+
+        assert_eq!(
+            handle_backward_jump(1000, 939, 60, policy, &mut last_fired),
+            Some(61)
+        );
+        assert!(last_fired.is_empty());
+    }
+
+    #[test]
+    fn skip_rewound_keeps_history() {
+        let mut last_fired = HashMap::from([("chime", DUE)]);
+        let policy = BackwardJumpPolicy::SkipRewound;
+        assert_eq!(
+            handle_backward_jump(5000, 1000, 60, policy, &mut last_fired),
+            Some(4000)
+        );
+        assert_eq!(last_fired.get("chime"), Some(&DUE));
+    }
+}
@@ -0,0 +1,13 @@
+// Alarm logic that does not touch the hardware, kept apart from the firmware
+// so it can be tested on the host with `cargo test` in this directory
+
+pub mod calendar;
+pub mod chime;
+pub mod collision;
+pub mod dedup;
+pub mod melody;
+pub mod morse;
+pub mod policy;
+pub mod schedule;
+pub mod scheduler;
+pub mod tone;
@@ -0,0 +1,132 @@
+// A single note of a melody
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Note {
+    pub frequency: u32,
+    pub duration_ms: u64,
+}
+
+// A built-in melody that alarms select by name
+pub struct MelodyInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub notes: &'static [Note],
+}
+
+// Look up a melody in a registry by name
+pub fn find_melody<'a>(melodies: &'a [MelodyInfo], name: &str) -> Option<&'a [Note]> {
+    melodies
+        .iter()
+        .find(|melody| melody.name == name)
+        .map(|melody| melody.notes)
+}
+
+// The named melody, or Err with `fallback` when the name is dangling, so the
+// caller can report it and still have something to play
+pub fn resolve_melody<'a>(
+    melodies: &'a [MelodyInfo],
+    name: &str,
+    fallback: &'a [Note],
+) -> Result<&'a [Note], &'a [Note]> {
+    find_melody(melodies, name).ok_or(fallback)
+}
+
+// Extra plays or beeps added on each repeat of an alarm, up to a cap
+#[derive(Clone, Copy, Debug)]
+pub struct RepeatGrowth {
+    pub step: u8,
+    pub max: u8,
+}
+
+// How many times to sound in the given repeat (counting from 0): `base`,
+// plus `step` more for each earlier repeat when growth is set, held to the
+// cap. A cap below `base` never shrinks the count.
+pub fn grown_count(base: u8, repeat: u8, growth: Option<RepeatGrowth>) -> u8 {
+    let Some(growth) = growth else {
+        return base;
+    };
+
+    let extra = repeat.saturating_mul(growth.step);
+    base.saturating_add(extra).min(growth.max.max(base))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHIME: &[Note] = &[Note {
+        frequency: 1000,
+        duration_ms: 500,
+    }];
+
+    const BLIP: &[Note] = &[Note {
+        frequency: 2500,
+        duration_ms: 100,
+    }];
+
+    const FALLBACK: &[Note] = &[Note {
+        frequency: 800,
+        duration_ms: 300,
+    }];
+
+    const MELODIES: &[MelodyInfo] = &[
+        MelodyInfo {
+            name: "chime",
+            description: "Single chime",
+            notes: CHIME,
+        },
+        MelodyInfo {
+            name: "blip",
+            description: "Short blip",
+            notes: BLIP,
+        },
+    ];
+
+    #[test]
+    fn finds_melodies_by_name() {
+        assert_eq!(find_melody(MELODIES, "chime"), Some(CHIME));
+        assert_eq!(find_melody(MELODIES, "blip"), Some(BLIP));
+        assert_eq!(find_melody(MELODIES, "Chime"), None);
+        assert_eq!(find_melody(MELODIES, ""), None);
+    }
+
+    #[test]
+    fn known_names_resolve_to_their_melody() {
+        assert_eq!(resolve_melody(MELODIES, "blip", FALLBACK), Ok(BLIP));
+    }
+
+    #[test]
+    fn dangling_names_fall_back() {
+        assert_eq!(resolve_melody(MELODIES, "deleted", FALLBACK), Err(FALLBACK));
+        assert_eq!(resolve_melody(&[], "chime", FALLBACK), Err(FALLBACK));
+    }
+
+    const GROWTH: Option<RepeatGrowth> = Some(RepeatGrowth { step: 1, max: 6 });
+
+    #[test]
+    fn count_grows_across_repeats() {
+        let counts: Vec<u8> = (0..8)
+            .map(|repeat| grown_count(1, repeat, GROWTH))
+            .collect();
+        assert_eq!(counts, [1, 2, 3, 4, 5, 6, 6, 6]);
+        assert!(counts.windows(2).all(|pair| pair[1] >= pair[0]));
+        assert!(counts[1] > counts[0]);
+    }
+
+    #[test]
+    fn growth_steps_and_caps() {
+        let growth = Some(RepeatGrowth { step: 2, max: 7 });
+        assert_eq!(grown_count(3, 0, growth), 3);
+        assert_eq!(grown_count(3, 1, growth), 5);
+        assert_eq!(grown_count(3, 2, growth), 7);
+        assert_eq!(grown_count(3, 200, growth), 7);
+    }
+
+    #[test]
+    fn no_growth_keeps_the_base() {
+        for repeat in 0..10 {
+            assert_eq!(grown_count(3, repeat, None), 3);
+        }
+        // A cap below the base leaves the base alone
+        assert_eq!(grown_count(3, 4, Some(RepeatGrowth { step: 1, max: 2 })), 3);
+    }
+}
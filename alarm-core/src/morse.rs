@@ -0,0 +1,139 @@
+// Morse code for a single character, as dots and dashes
+pub fn morse_code(c: char) -> Option<&'static str> {
+    let code = match c.to_ascii_uppercase() {
+        'A' => ".-",
+        'B' => "-...",
+        'C' => "-.-.",
+        'D' => "-..",
+        'E' => ".",
+        'F' => "..-.",
+        'G' => "--.",
+        'H' => "....",
+        'I' => "..",
+        'J' => ".---",
+        'K' => "-.-",
+        'L' => ".-..",
+        'M' => "--",
+        'N' => "-.",
+        'O' => "---",
+        'P' => ".--.",
+        'Q' => "--.-",
+        'R' => ".-.",
+        'S' => "...",
+        'T' => "-",
+        'U' => "..-",
+        'V' => "...-",
+        'W' => ".--",
+        'X' => "-..-",
+        'Y' => "-.--",
+        'Z' => "--..",
+        '0' => "-----",
+        '1' => ".----",
+        '2' => "..---",
+        '3' => "...--",
+        '4' => "....-",
+        '5' => ".....",
+        '6' => "-....",
+        '7' => "--...",
+        '8' => "---..",
+        '9' => "----.",
+        '.' => ".-.-.-",
+        ',' => "--..--",
+        '?' => "..--..",
+        '/' => "-..-.",
+        '=' => "-...-",
+        _ => return None,
+    };
+    Some(code)
+}
+
+// Length of one Morse unit (a dot) at the given speed, using the 50-unit
+// word "PARIS"
+pub fn morse_unit_ms(wpm: u64) -> u64 {
+    1200 / wpm
+}
+
+// At most the first `max_chars` characters of a message
+pub fn truncate_message(text: &str, max_chars: usize) -> &str {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Encode a message the way play_morse walks it, characters separated by
+    // spaces and words by " / "
+    fn encode(text: &str) -> String {
+        text.split_whitespace()
+            .map(|word| {
+                word.chars()
+                    .filter_map(morse_code)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join(" / ")
+    }
+
+    #[test]
+    fn encodes_letters_and_digits() {
+        assert_eq!(morse_code('E'), Some("."));
+        assert_eq!(morse_code('T'), Some("-"));
+        assert_eq!(morse_code('Q'), Some("--.-"));
+        assert_eq!(morse_code('0'), Some("-----"));
+        assert_eq!(morse_code('7'), Some("--..."));
+        assert_eq!(morse_code('?'), Some("..--.."));
+    }
+
+    #[test]
+    fn ignores_case() {
+        for c in 'a'..='z' {
+            assert_eq!(morse_code(c), morse_code(c.to_ascii_uppercase()));
+            assert!(morse_code(c).is_some());
+        }
+    }
+
+    #[test]
+    fn rejects_unsupported_characters() {
+        assert_eq!(morse_code('#'), None);
+        assert_eq!(morse_code(' '), None);
+        assert_eq!(morse_code('\u{e9}'), None);
+    }
+
+    #[test]
+    fn codes_are_unique() {
+        let chars = ('A'..='Z').chain('0'..='9').chain(".,?/=".chars());
+        let mut codes: Vec<_> = chars.filter_map(morse_code).collect();
+        let count = codes.len();
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes.len(), count);
+    }
+
+    #[test]
+    fn encodes_messages() {
+        assert_eq!(encode("SOS"), "... --- ...");
+        assert_eq!(encode("ip 42"), ".. .--. / ....- ..---");
+        assert_eq!(encode("a#b"), ".- -...");
+    }
+
+    #[test]
+    fn unit_follows_speed() {
+        assert_eq!(morse_unit_ms(15), 80);
+        assert_eq!(morse_unit_ms(20), 60);
+    }
+
+    #[test]
+    fn truncates_long_messages() {
+        assert_eq!(truncate_message("SOS", 32), "SOS");
+        assert_eq!(truncate_message("HELLO WORLD", 5), "HELLO");
+        assert_eq!(truncate_message("HELLO", 5), "HELLO");
+        assert_eq!(truncate_message("", 5), "");
+        // Cuts on character boundaries, not bytes
+        assert_eq!(truncate_message("\u{e9}\u{e9}\u{e9}", 2), "\u{e9}\u{e9}");
+    }
+}
@@ -0,0 +1,263 @@
+use crate::calendar::is_skip_date;
+use crate::schedule::{policy_allows, AlarmType, PolicyRule};
+
+// Who has to be around for an alarm to sound, ignored without a presence sensor
+#[derive(Clone, Copy, Debug)]
+pub enum Presence {
+    Any,
+    Present, // Someone seen recently
+    Absent,  // Nobody seen recently
+}
+
+// Where the device is powered from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PowerSource {
+    Usb,
+    Battery,
+}
+
+impl PowerSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            PowerSource::Usb => "USB power",
+            PowerSource::Battery => "battery",
+        }
+    }
+}
+
+// Which power source alarms may sound on, ignored without power sensing
+#[derive(Clone, Copy, Debug)]
+pub enum PowerGate {
+    Any,
+    UsbOnly,
+    BatteryOnly, // Pause alarms while charging
+}
+
+// Sensed surroundings that alarms can be gated on
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Conditions {
+    pub present: Option<bool>,             // None without a presence sensor
+    pub power_source: Option<PowerSource>, // None without power sensing
+}
+
+// Why an alarm that came due did not sound
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Suppression {
+    SkipDate,   // On one of the skip dates
+    QuietHours, // Not permitted by the policy rules at this hour
+    Presence,   // Presence setting not met
+    Power,      // Not permitted by the power gate on this power source
+}
+
+impl Suppression {
+    pub fn name(self) -> &'static str {
+        match self {
+            Suppression::SkipDate => "skip date",
+            Suppression::QuietHours => "quiet hours",
+            Suppression::Presence => "presence",
+            Suppression::Power => "power source",
+        }
+    }
+}
+
+// Everything that decides whether an alarm that came due may sound
+#[derive(Clone, Copy)]
+pub struct Policy<'a> {
+    pub rules: &'a [PolicyRule],
+    pub skip_dates: &'a [(u32, u32, u32)], // See calendar::is_skip_date
+    pub presence: fn(AlarmType) -> Presence,
+    pub power_gate: PowerGate,
+}
+
+// Whether an alarm of the given type may sound at the given local time under
+// the sensed conditions, or why not
+pub fn evaluate_policy(
+    policy: &Policy,
+    now_with_tz: u64,
+    alarm_type: AlarmType,
+    conditions: Conditions,
+) -> Result<(), Suppression> {
+    if is_skip_date(policy.skip_dates, now_with_tz / 86_400) {
+        return Err(Suppression::SkipDate);
+    }
+
+    if let Some(present) = conditions.present {
+        let allowed = match (policy.presence)(alarm_type) {
+            Presence::Any => true,
+            Presence::Present => present,
+            Presence::Absent => !present,
+        };
+        if !allowed {
+            return Err(Suppression::Presence);
+        }
+    }
+
+    if let Some(power_source) = conditions.power_source {
+        let allowed = match policy.power_gate {
+            PowerGate::Any => true,
+            PowerGate::UsbOnly => power_source == PowerSource::Usb,
+            PowerGate::BatteryOnly => power_source == PowerSource::Battery,
+        };
+        if !allowed {
+            return Err(Suppression::Power);
+        }
+    }
+
+    let hour = (now_with_tz / 3600) % 24;
+    if !policy_allows(policy.rules, hour, alarm_type) {
+        return Err(Suppression::QuietHours);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::days_from_civil;
+
+    // Everything from 07:00, only the bedtime chime from 22:00, quiet from 23:00
+    const RULES: &[PolicyRule] = &[
+        PolicyRule {
+            start_hour: 7,
+            end_hour: 22,
+            hour_chime: true,
+            reminder: true,
+            bedtime: true,
+            quarter_chime: true,
+        },
+        PolicyRule {
+            start_hour: 22,
+            end_hour: 23,
+            hour_chime: false,
+            reminder: false,
+            bedtime: true,
+            quarter_chime: false,
+        },
+    ];
+
+    // The reminder only with someone around, the rest regardless
+    fn presence(alarm_type: AlarmType) -> Presence {
+        match alarm_type {
+            AlarmType::Reminder => Presence::Present,
+            _ => Presence::Any,
+        }
+    }
+
+    const POLICY: Policy = Policy {
+        rules: RULES,
+        skip_dates: &[(0, 12, 25)],
+        presence,
+        power_gate: PowerGate::BatteryOnly,
+    };
+
+    // Local time at the given hour on the given date
+    fn at(year: u32, month: u32, day: u32, hour: u64) -> u64 {
+        days_from_civil(year, month, day) * 86_400 + hour * 3600
+    }
+
+    fn evaluate(
+        now_with_tz: u64,
+        alarm_type: AlarmType,
+        conditions: Conditions,
+    ) -> Result<(), Suppression> {
+        evaluate_policy(&POLICY, now_with_tz, alarm_type, conditions)
+    }
+
+    #[test]
+    fn allowed_inside_the_rules() {
+        let noon = at(2024, 3, 15, 12);
+        assert_eq!(
+            evaluate(noon, AlarmType::HourChime, Conditions::default()),
+            Ok(())
+        );
+        let present_on_battery = Conditions {
+            present: Some(true),
+            power_source: Some(PowerSource::Battery),
+        };
+        assert_eq!(
+            evaluate(noon, AlarmType::Reminder, present_on_battery),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn skip_date_suppresses_everything() {
+        let noon = at(2024, 12, 25, 12);
+        assert_eq!(
+            evaluate(noon, AlarmType::HourChime, Conditions::default()),
+            Err(Suppression::SkipDate)
+        );
+        // A skip date wins over every other reason
+        assert_eq!(
+            evaluate(
+                at(2024, 12, 25, 3),
+                AlarmType::Reminder,
+                Conditions {
+                    present: Some(false),
+                    power_source: Some(PowerSource::Usb),
+                }
+            ),
+            Err(Suppression::SkipDate)
+        );
+    }
+
+    #[test]
+    fn quiet_hours_outside_the_rules() {
+        for hour in [0, 6, 23] {
+            assert_eq!(
+                evaluate(
+                    at(2024, 3, 15, hour),
+                    AlarmType::HourChime,
+                    Conditions::default()
+                ),
+                Err(Suppression::QuietHours)
+            );
+        }
+    }
+
+    #[test]
+    fn policy_disables_the_alarm_type() {
+        let late = at(2024, 3, 15, 22);
+        assert_eq!(
+            evaluate(late, AlarmType::HourChime, Conditions::default()),
+            Err(Suppression::QuietHours)
+        );
+        assert_eq!(
+            evaluate(late, AlarmType::Bedtime, Conditions::default()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn presence_setting_must_be_met() {
+        let noon = at(2024, 3, 15, 12);
+        let absent = Conditions {
+            present: Some(false),
+            power_source: None,
+        };
+        assert_eq!(
+            evaluate(noon, AlarmType::Reminder, absent),
+            Err(Suppression::Presence)
+        );
+        assert_eq!(evaluate(noon, AlarmType::HourChime, absent), Ok(()));
+        // Without a sensor the setting is ignored
+        assert_eq!(
+            evaluate(noon, AlarmType::Reminder, Conditions::default()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn power_gate_disables_the_alarm() {
+        let noon = at(2024, 3, 15, 12);
+        let on_usb = Conditions {
+            present: None,
+            power_source: Some(PowerSource::Usb),
+        };
+        assert_eq!(
+            evaluate(noon, AlarmType::HourChime, on_usb),
+            Err(Suppression::Power)
+        );
+    }
+}
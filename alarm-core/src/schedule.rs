@@ -0,0 +1,189 @@
+// The kinds of scheduled alarm the policy can permit or suppress
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AlarmType {
+    HourChime,
+    Reminder,
+    Bedtime,
+    QuarterChime,
+}
+
+impl AlarmType {
+    pub fn name(self) -> &'static str {
+        match self {
+            AlarmType::HourChime => "hourly chime",
+            AlarmType::Reminder => "10-min reminder",
+            AlarmType::Bedtime => "bedtime chime",
+            AlarmType::QuarterChime => "Westminster chime",
+        }
+    }
+
+    // Higher values win when alarms collide under the HighestPriority policy
+    pub fn priority(self) -> u8 {
+        match self {
+            AlarmType::HourChime => 3,
+            AlarmType::Bedtime => 2,
+            AlarmType::Reminder => 1,
+            AlarmType::QuarterChime => 0,
+        }
+    }
+}
+
+// Anything that sounds at a scheduled time, for duplicate-fire tracking
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Trigger {
+    Alarm(AlarmType),
+    PreWarning(AlarmType),
+}
+
+// When each alarm comes due
+#[derive(Clone, Copy)]
+pub struct Schedule {
+    pub hour_chime_minute: u64, // Minute past each hour
+    pub reminder_minute: u64,   // Minute past each hour
+    pub bedtime: Option<u64>,   // Minute of the day, None when disabled
+    pub quarter_chime: bool,    // Westminster chime at each quarter hour
+}
+
+impl Schedule {
+    // Each alarm's due time in the current hour (for bedtime, only during its
+    // hour), in minutes since the epoch and in the order they are sounded.
+    // A due time may still lie ahead; should_fire decides when it is reached.
+    pub fn due_times(&self, now_minute: u64) -> Vec<(AlarmType, u64)> {
+        let hour_start = now_minute - now_minute % 60;
+        let day_start = now_minute - now_minute % 1440;
+        let mut due = Vec::new();
+
+        // Westminster chime at each quarter, queued ahead of the hour strike
+        if self.quarter_chime && now_minute % 15 == 0 {
+            due.push((AlarmType::QuarterChime, now_minute));
+        }
+        due.push((AlarmType::HourChime, hour_start + self.hour_chime_minute));
+        due.push((AlarmType::Reminder, hour_start + self.reminder_minute));
+        if let Some(bedtime) = self.bedtime {
+            if hour_start - day_start == bedtime / 60 * 60 {
+                due.push((AlarmType::Bedtime, day_start + bedtime));
+            }
+        }
+
+        due
+    }
+}
+
+// Alarm types permitted from start_hour up to (not including) end_hour.
+// Ranges may wrap past midnight, e.g. 22 to 7.
+pub struct PolicyRule {
+    pub start_hour: u64,
+    pub end_hour: u64,
+    pub hour_chime: bool,
+    pub reminder: bool,
+    pub bedtime: bool,
+    pub quarter_chime: bool,
+}
+
+// Whether the first rule covering `hour` permits the alarm. Uncovered hours are quiet.
+pub fn policy_allows(rules: &[PolicyRule], hour: u64, alarm_type: AlarmType) -> bool {
+    let rule = rules.iter().find(|rule| {
+        if rule.start_hour <= rule.end_hour {
+            (rule.start_hour..rule.end_hour).contains(&hour)
+        } else {
+            hour >= rule.start_hour || hour < rule.end_hour
+        }
+    });
+
+    match (rule, alarm_type) {
+        (Some(rule), AlarmType::HourChime) => rule.hour_chime,
+        (Some(rule), AlarmType::Reminder) => rule.reminder,
+        (Some(rule), AlarmType::Bedtime) => rule.bedtime,
+        (Some(rule), AlarmType::QuarterChime) => rule.quarter_chime,
+        (None, _) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn rule(start_hour: u64, end_hour: u64, allowed: bool) -> PolicyRule {
+        PolicyRule {
+            start_hour,
+            end_hour,
+            hour_chime: allowed,
+            reminder: allowed,
+            bedtime: allowed,
+            quarter_chime: allowed,
+        }
+    }
+
+    // Allowed hours for the hourly chime under the given rules
+    fn chime_hours(rules: &[PolicyRule]) -> Vec<u64> {
+        (0..24)
+            .filter(|&hour| policy_allows(rules, hour, AlarmType::HourChime))
+            .collect()
+    }
+
+    #[test]
+    fn plain_range_excludes_its_end() {
+        let rules = [rule(7, 22, true)];
+        assert_eq!(chime_hours(&rules), (7..22).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn range_wraps_past_midnight() {
+        // 22 to 7 covers 22:00 up to 06:59, so 23:59 and 00:00 are in and
+        // 07:00 is out
+        let rules = [rule(22, 7, true)];
+        for (hour, allowed) in [
+            (21, false),
+            (22, true),
+            (23, true),
+            (0, true),
+            (6, true),
+            (7, false),
+        ] {
+            assert_eq!(
+                policy_allows(&rules, hour, AlarmType::Reminder),
+                allowed,
+                "hour {}",
+                hour
+            );
+        }
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        // A quiet night rule listed first overrides the all-day rule under it
+        let rules = [rule(22, 7, false), rule(0, 24, true)];
+        assert_eq!(chime_hours(&rules), (7..22).collect::<Vec<_>>());
+
+        // Listed the other way round, the all-day rule hides the night rule
+        let rules = [rule(0, 24, true), rule(22, 7, false)];
+        assert_eq!(chime_hours(&rules).len(), 24);
+    }
+
+    #[test]
+    fn uncovered_hours_are_quiet() {
+        assert!(chime_hours(&[]).is_empty());
+
+        let rules = [rule(9, 12, true), rule(14, 17, true)];
+        assert!(!policy_allows(&rules, 8, AlarmType::HourChime));
+        assert!(!policy_allows(&rules, 12, AlarmType::HourChime));
+        assert!(!policy_allows(&rules, 13, AlarmType::HourChime));
+        assert!(policy_allows(&rules, 14, AlarmType::HourChime));
+    }
+
+    #[test]
+    fn rules_gate_each_alarm_type() {
+        let rules = [PolicyRule {
+            start_hour: 0,
+            end_hour: 7,
+            hour_chime: false,
+            reminder: true,
+            bedtime: false,
+            quarter_chime: false,
+        }];
+        assert!(policy_allows(&rules, 3, AlarmType::Reminder));
+        assert!(!policy_allows(&rules, 3, AlarmType::HourChime));
+        assert!(!policy_allows(&rules, 3, AlarmType::Bedtime));
+        assert!(!policy_allows(&rules, 3, AlarmType::QuarterChime));
+    }
+}
@@ -0,0 +1,218 @@
+use crate::chime::{hour_chime_repeats, MidnightChime};
+use crate::collision::{resolve_collisions, CollisionPolicy};
+use crate::dedup::claim_due;
+use crate::policy::{evaluate_policy, Conditions, Policy, Suppression};
+use crate::schedule::{AlarmType, Schedule, Trigger};
+use std::collections::HashMap;
+
+// Everything that decides which alarms sound and how many times
+#[derive(Clone, Copy)]
+pub struct AlarmConfig<'a> {
+    pub schedule: Schedule,
+    pub policy: Policy<'a>,
+    pub collisions: CollisionPolicy,
+    pub midnight_chime: MidnightChime,
+    pub noon_chime: bool,      // Whether noon chimes 12 or stays silent
+    pub max_chime_repeats: u8, // See chime::hour_chime_repeats
+    pub reminder_repeats: u8,
+}
+
+// An alarm that came due, with how many times it should sound
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DueAlarm {
+    pub alarm_type: AlarmType,
+    pub due_minute: u64, // Local minutes since the epoch
+    pub repeat_count: u8,
+}
+
+// What one pass over the schedule decided, each list in schedule order
+#[derive(Debug, Default)]
+pub struct Tick {
+    pub fired: Vec<DueAlarm>,                      // To sound now
+    pub suppressed: Vec<(AlarmType, Suppression)>, // Blocked by the policy
+    pub silent: Vec<DueAlarm>,                     // Hour chimes configured to 0 repeats
+    pub skipped: Vec<DueAlarm>,                    // Lost a collision to the fired alarm
+}
+
+// Decide which alarms sound at the given local time: claim each due time not
+// yet fired, check it against the policy, count the hour chime's repeats and
+// resolve collisions. Claimed alarms are recorded in `last_fired` whatever
+// the outcome, so each due time is only decided once.
+pub fn tick(
+    config: &AlarmConfig,
+    now_with_tz: u64,
+    conditions: Conditions,
+    last_fired: &mut HashMap<Trigger, u64>,
+) -> Tick {
+    let now_minute = now_with_tz / 60;
+    let mut result = Tick::default();
+    let mut fired = Vec::new();
+
+    for (alarm_type, due_minute) in config.schedule.due_times(now_minute) {
+        if !claim_due(
+            Trigger::Alarm(alarm_type),
+            due_minute,
+            now_minute,
+            last_fired,
+        ) {
+            continue;
+        }
+
+        if let Err(reason) = evaluate_policy(&config.policy, now_with_tz, alarm_type, conditions) {
+            result.suppressed.push((alarm_type, reason));
+            continue;
+        }
+
+        let repeat_count = match alarm_type {
+            AlarmType::HourChime => hour_chime_repeats(
+                (due_minute / 60 % 24) as u8,
+                config.midnight_chime,
+                config.noon_chime,
+                config.max_chime_repeats,
+            ),
+            AlarmType::Reminder => config.reminder_repeats,
+            AlarmType::Bedtime | AlarmType::QuarterChime => 1,
+        };
+        let alarm = DueAlarm {
+            alarm_type,
+            due_minute,
+            repeat_count,
+        };
+        if repeat_count == 0 {
+            result.silent.push(alarm);
+        } else {
+            fired.push(alarm);
+        }
+    }
+
+    (result.fired, result.skipped) =
+        resolve_collisions(config.collisions, fired, |alarm| alarm.alarm_type);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::{PowerGate, Presence};
+    use crate::schedule::PolicyRule;
+
+    const ALL_DAY: &[PolicyRule] = &[PolicyRule {
+        start_hour: 0,
+        end_hour: 24,
+        hour_chime: true,
+        reminder: true,
+        bedtime: true,
+        quarter_chime: true,
+    }];
+
+    fn config(collisions: CollisionPolicy) -> AlarmConfig<'static> {
+        AlarmConfig {
+            schedule: Schedule {
+                hour_chime_minute: 0,
+                reminder_minute: 10,
+                bedtime: None,
+                quarter_chime: true,
+            },
+            policy: Policy {
+                rules: ALL_DAY,
+                skip_dates: &[],
+                presence: |_| Presence::Any,
+                power_gate: PowerGate::Any,
+            },
+            collisions,
+            midnight_chime: MidnightChime::Silent,
+            noon_chime: true,
+            max_chime_repeats: 12,
+            reminder_repeats: 3,
+        }
+    }
+
+    // Local time on the first day of the epoch
+    fn at(hour: u64, minute: u64) -> u64 {
+        hour * 3600 + minute * 60
+    }
+
+    #[test]
+    fn counts_repeats_and_fires_once() {
+        let config = config(CollisionPolicy::PlayAll);
+        let mut last_fired = HashMap::new();
+
+        let first = tick(&config, at(15, 0), Conditions::default(), &mut last_fired);
+        let types: Vec<_> = first
+            .fired
+            .iter()
+            .map(|a| (a.alarm_type, a.repeat_count))
+            .collect();
+        assert_eq!(
+            types,
+            [(AlarmType::QuarterChime, 1), (AlarmType::HourChime, 3)]
+        );
+
+        // Later in the same minute nothing fires again
+        let again = tick(
+            &config,
+            at(15, 0) + 30,
+            Conditions::default(),
+            &mut last_fired,
+        );
+        assert!(again.fired.is_empty());
+
+        let reminder = tick(&config, at(15, 10), Conditions::default(), &mut last_fired);
+        assert_eq!(reminder.fired[0].alarm_type, AlarmType::Reminder);
+        assert_eq!(reminder.fired[0].repeat_count, 3);
+    }
+
+    #[test]
+    fn silent_midnight_is_reported_not_fired() {
+        let config = config(CollisionPolicy::PlayAll);
+        let result = tick(
+            &config,
+            at(0, 0),
+            Conditions::default(),
+            &mut HashMap::new(),
+        );
+        assert_eq!(result.fired.len(), 1);
+        assert_eq!(result.fired[0].alarm_type, AlarmType::QuarterChime);
+        assert_eq!(result.silent.len(), 1);
+        assert_eq!(result.silent[0].alarm_type, AlarmType::HourChime);
+    }
+
+    #[test]
+    fn collisions_are_resolved() {
+        let config = config(CollisionPolicy::HighestPriority);
+        let result = tick(
+            &config,
+            at(9, 0),
+            Conditions::default(),
+            &mut HashMap::new(),
+        );
+        assert_eq!(result.fired.len(), 1);
+        assert_eq!(result.fired[0].alarm_type, AlarmType::HourChime);
+        assert_eq!(result.skipped[0].alarm_type, AlarmType::QuarterChime);
+    }
+
+    #[test]
+    fn suppressed_alarms_are_claimed() {
+        let mut config = config(CollisionPolicy::PlayAll);
+        config.policy.skip_dates = &[(1970, 1, 1)];
+        let mut last_fired = HashMap::new();
+
+        let result = tick(&config, at(9, 10), Conditions::default(), &mut last_fired);
+        assert!(result.fired.is_empty());
+        assert_eq!(
+            result.suppressed,
+            [
+                (AlarmType::HourChime, Suppression::SkipDate),
+                (AlarmType::Reminder, Suppression::SkipDate)
+            ]
+        );
+
+        let again = tick(
+            &config,
+            at(9, 10) + 30,
+            Conditions::default(),
+            &mut last_fired,
+        );
+        assert!(again.suppressed.is_empty());
+    }
+}
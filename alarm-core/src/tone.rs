@@ -0,0 +1,116 @@
+// Timing math for square-wave tones, kept apart from the GPIO toggling
+
+// Length of one FreeRTOS tick in microseconds
+pub const fn tick_period_us(tick_rate_hz: u32) -> u64 {
+    1_000_000 / tick_rate_hz as u64
+}
+
+// Half-period of a square wave in microseconds, or None for a steady 0 Hz tone
+pub fn half_period_us(freq_hz: u32) -> Option<u64> {
+    if freq_hz == 0 {
+        return None;
+    }
+
+    // Frequencies above 500 kHz would round down to a zero-length half-period
+    Some((500_000 / freq_hz as u64).max(1))
+}
+
+// Number of full cycles needed to fill `duration_us`, the last one running
+// over when the duration is not a whole number of periods
+pub fn cycles_for_duration(half_period_us: u64, duration_us: u64) -> u64 {
+    duration_us.div_ceil(2 * half_period_us)
+}
+
+// High time of the current cycle: a square wave, narrowed within `ramp_us`
+// of either end of the tone
+pub fn ramped_high_us(half_period_us: u64, now_us: u64, duration_us: u64, ramp_us: u64) -> u64 {
+    let edge_us = now_us.min(duration_us.saturating_sub(now_us));
+    if edge_us >= ramp_us {
+        return half_period_us;
+    }
+
+    (half_period_us * edge_us / ramp_us).max(1)
+}
+
+// Whether a half-period is too short to sleep through and must be spun,
+// given the shortest sleep the scheduler can do
+pub fn should_spin(half_period_us: u64, min_sleep_us: u64) -> bool {
+    half_period_us < min_sleep_us
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_period_across_the_range() {
+        assert_eq!(half_period_us(1), Some(500_000));
+        assert_eq!(half_period_us(440), Some(1136));
+        assert_eq!(half_period_us(1000), Some(500));
+        assert_eq!(half_period_us(2000), Some(250));
+        assert_eq!(half_period_us(10_000), Some(50));
+    }
+
+    #[test]
+    fn zero_hz_is_steady() {
+        assert_eq!(half_period_us(0), None);
+    }
+
+    #[test]
+    fn half_period_never_reaches_zero() {
+        assert_eq!(half_period_us(500_000), Some(1));
+        assert_eq!(half_period_us(1_000_000), Some(1));
+        assert_eq!(half_period_us(u32::MAX), Some(1));
+    }
+
+    #[test]
+    fn cycles_fill_the_duration() {
+        assert_eq!(cycles_for_duration(500, 1_000_000), 1000);
+        assert_eq!(cycles_for_duration(50, 100_000), 1000);
+        // 440 Hz does not divide a second evenly, so the last cycle runs over
+        assert_eq!(cycles_for_duration(1136, 1_000_000), 441);
+        assert_eq!(cycles_for_duration(500_000, 1_500_000), 2);
+        assert_eq!(cycles_for_duration(500, 0), 0);
+    }
+
+    #[test]
+    fn tick_period_follows_tick_rate() {
+        assert_eq!(tick_period_us(1000), 1000);
+        assert_eq!(tick_period_us(100), 10_000);
+    }
+
+    #[test]
+    fn spins_only_below_one_tick() {
+        let tick_us = tick_period_us(1000);
+        assert!(should_spin(half_period_us(2000).unwrap(), tick_us));
+        assert!(should_spin(tick_us - 1, tick_us));
+        assert!(!should_spin(tick_us, tick_us));
+        assert!(!should_spin(half_period_us(100).unwrap(), tick_us));
+
+        // A slower tick pushes mid frequencies into the spin loop too
+        assert!(should_spin(
+            half_period_us(100).unwrap(),
+            tick_period_us(100)
+        ));
+        assert!(!should_spin(
+            half_period_us(1).unwrap(),
+            tick_period_us(100)
+        ));
+    }
+
+    #[test]
+    fn ramp_narrows_the_edges() {
+        let (half, duration, ramp) = (250, 1_010_000, 5000);
+        assert_eq!(ramped_high_us(half, 500_000, duration, ramp), half);
+        assert_eq!(ramped_high_us(half, 2500, duration, ramp), 125);
+        assert_eq!(ramped_high_us(half, duration - 2500, duration, ramp), 125);
+        assert_eq!(ramped_high_us(half, 0, duration, ramp), 1);
+        assert_eq!(ramped_high_us(half, ramp, duration, ramp), half);
+    }
+
+    #[test]
+    fn no_ramp_is_a_square_wave() {
+        assert_eq!(ramped_high_us(250, 0, 1_000_000, 0), 250);
+        assert_eq!(ramped_high_us(250, 999_999, 1_000_000, 0), 250);
+    }
+}
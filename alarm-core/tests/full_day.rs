@@ -0,0 +1,121 @@
+// Runs the scheduler over a simulated day, the way the firmware's main loop
+// drives it, and checks exactly which alarms sound and when
+
+use alarm_core::calendar::days_from_civil;
+use alarm_core::chime::MidnightChime;
+use alarm_core::collision::CollisionPolicy;
+use alarm_core::policy::{Conditions, Policy, PowerGate, Presence};
+use alarm_core::schedule::{AlarmType, PolicyRule, Schedule, Trigger};
+use alarm_core::scheduler::{tick, AlarmConfig};
+use std::collections::HashMap;
+
+// Everything from 07:00, only the bedtime chime from 22:00, quiet from 23:00
+const POLICY: &[PolicyRule] = &[
+    PolicyRule {
+        start_hour: 7,
+        end_hour: 22,
+        hour_chime: true,
+        reminder: true,
+        bedtime: true,
+        quarter_chime: true,
+    },
+    PolicyRule {
+        start_hour: 22,
+        end_hour: 23,
+        hour_chime: false,
+        reminder: false,
+        bedtime: true,
+        quarter_chime: false,
+    },
+];
+
+const ALARMS: AlarmConfig = AlarmConfig {
+    schedule: Schedule {
+        hour_chime_minute: 0,
+        reminder_minute: 10,
+        bedtime: Some(22 * 60 + 30),
+        quarter_chime: true,
+    },
+    policy: Policy {
+        rules: POLICY,
+        skip_dates: &[(0, 1, 1)],
+        presence: |_| Presence::Any,
+        power_gate: PowerGate::Any,
+    },
+    collisions: CollisionPolicy::PlayAll,
+    midnight_chime: MidnightChime::Twelve,
+    noon_chime: true,
+    max_chime_repeats: 12,
+    reminder_repeats: 1,
+};
+
+// The main loop's alarm pass for one reading of the local clock, recording
+// what would be sent to the buzzer
+fn poll(
+    now_with_tz: u64,
+    last_fired: &mut HashMap<Trigger, u64>,
+    buzzer: &mut Vec<(u64, AlarmType)>,
+) {
+    let now_minute = now_with_tz / 60;
+    for alarm in tick(&ALARMS, now_with_tz, Conditions::default(), last_fired).fired {
+        buzzer.push((now_minute % 1440, alarm.alarm_type));
+    }
+}
+
+// Step a clock through one local day from midnight, `step_secs` at a time
+fn run_day(day: u64, step_secs: u64) -> Vec<(u64, AlarmType)> {
+    let mut last_fired = HashMap::new();
+    let mut buzzer = Vec::new();
+    let start = day * 86_400;
+    let mut now = start;
+    while now < start + 86_400 {
+        poll(now, &mut last_fired, &mut buzzer);
+        now += step_secs;
+    }
+    buzzer
+}
+
+fn expected_day() -> Vec<(u64, AlarmType)> {
+    let mut expected = Vec::new();
+    for hour in 7..22 {
+        let minute = hour * 60;
+        expected.push((minute, AlarmType::QuarterChime));
+        expected.push((minute, AlarmType::HourChime));
+        expected.push((minute + 10, AlarmType::Reminder));
+        expected.push((minute + 15, AlarmType::QuarterChime));
+        expected.push((minute + 30, AlarmType::QuarterChime));
+        expected.push((minute + 45, AlarmType::QuarterChime));
+    }
+    expected.push((22 * 60 + 30, AlarmType::Bedtime));
+    expected
+}
+
+#[test]
+fn full_day_fires_the_configured_schedule() {
+    // A pass every second, where the device makes one every half second
+    let fired = run_day(days_from_civil(2024, 3, 15), 1);
+    assert_eq!(fired, expected_day());
+}
+
+#[test]
+fn slow_loop_fires_each_alarm_once() {
+    // A loop that only gets round every 50 s still fires each alarm once, in
+    // the minute it is reached, apart from the quarter chime, which only
+    // sounds on the quarter itself
+    let fired = run_day(days_from_civil(2024, 3, 15), 50);
+    let on_time = |(minute, alarm_type): &(u64, AlarmType)| {
+        *alarm_type != AlarmType::QuarterChime || minute % 15 == 0
+    };
+    assert!(fired.iter().all(on_time));
+
+    let count = |alarm_type| fired.iter().filter(|(_, a)| *a == alarm_type).count();
+    assert_eq!(count(AlarmType::HourChime), 15);
+    assert_eq!(count(AlarmType::Reminder), 15);
+    assert_eq!(count(AlarmType::Bedtime), 1);
+}
+
+#[test]
+fn skip_date_stays_silent() {
+    let fired = run_day(days_from_civil(2025, 1, 1), 1);
+    assert!(fired.is_empty());
+}
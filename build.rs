@@ -1,3 +1,65 @@
+use std::env;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 fn main() {
     embuild::espidf::sysenv::output();
+
+    // Time used to seed the clock when the device starts offline. This
+    // script only reruns when HEAD moves (see below), so rather than a build
+    // time that would go stale across incremental builds, it is the commit
+    // time of HEAD, or the time of the build outside a checkout. Set
+    // BUILD_EPOCH_SECS to pin it, e.g. for reproducible builds.
+    println!("cargo:rerun-if-env-changed=BUILD_EPOCH_SECS");
+    let build_secs = env::var("BUILD_EPOCH_SECS")
+        .ok()
+        .or_else(|| git(&["log", "-1", "--format=%ct"]))
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        });
+    println!("cargo:rustc-env=BUILD_EPOCH_SECS={}", build_secs);
+
+    // Git commit reported at boot, with a "-dirty" suffix for uncommitted
+    // changes, "unknown" when not building from a checkout. Set GIT_HASH to
+    // override it.
+    println!("cargo:rerun-if-env-changed=GIT_HASH");
+    let git_hash = env::var("GIT_HASH")
+        .ok()
+        .or_else(|| git(&["describe", "--always", "--dirty"]))
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=GIT_HASH={}", git_hash);
+
+    // Rerun when HEAD moves, whether by switching branch or committing on
+    // the current one, and when the index or sources change, so the dirty
+    // suffix stays current. A missing file would force a rerun on every
+    // build, so only watch the ones that exist.
+    println!("cargo:rerun-if-changed=src");
+    if let Some(git_dir) = git(&["rev-parse", "--git-dir"]) {
+        let git_dir = Path::new(&git_dir);
+        let head_ref = git(&["symbolic-ref", "-q", "HEAD"]);
+        let watched = ["HEAD", "packed-refs", "index"]
+            .into_iter()
+            .chain(head_ref.as_deref())
+            .map(|path| git_dir.join(path))
+            .filter(|path| path.exists());
+        for path in watched {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+    }
+}
+
+// Output of a git command, trimmed, or None if git is missing or it failed
+fn git(args: &[&str]) -> Option<String> {
+    Command::new("git")
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|output| output.trim().to_string())
 }
@@ -1,175 +1,2284 @@
-use anyhow::Result;
+use alarm_core::calendar::{civil_from_days, days_from_civil, is_skip_date};
+use alarm_core::chime::MidnightChime;
+use alarm_core::collision::CollisionPolicy;
+use alarm_core::dedup::{already_fired, handle_backward_jump, seed_fired, BackwardJumpPolicy};
+use alarm_core::melody::{grown_count, resolve_melody, MelodyInfo, Note, RepeatGrowth};
+use alarm_core::morse::{morse_code, morse_unit_ms, truncate_message};
+use alarm_core::policy::{evaluate_policy, Conditions, Policy, PowerGate, PowerSource, Presence};
+use alarm_core::schedule::{AlarmType, PolicyRule, Schedule, Trigger};
+use alarm_core::scheduler::{self, AlarmConfig, DueAlarm};
+use alarm_core::tone::{
+    cycles_for_duration, half_period_us, ramped_high_us, should_spin, tick_period_us,
+};
+use anyhow::{Context, Result};
 use esp_idf_svc::eventloop::EspSystemEventLoop;
 use esp_idf_svc::hal;
-use esp_idf_svc::nvs::EspDefaultNvsPartition;
-use esp_idf_svc::sntp::{EspSntp, SyncStatus};
+use esp_idf_svc::log::EspLogger;
+use esp_idf_svc::mdns::EspMdns;
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs};
+use esp_idf_svc::sntp::{EspSntp, SntpConf, SyncStatus};
+use esp_idf_svc::sys::esp;
+use esp_idf_svc::wifi::{AccessPointConfiguration, AuthMethod, ClientConfiguration, Configuration};
 use esp_idf_svc::wifi::{BlockingWifi, EspWifi};
-use esp_idf_svc::wifi::{ClientConfiguration, Configuration};
-use hal::gpio::{Output, OutputPin, PinDriver};
+use hal::adc::oneshot::config::{AdcChannelConfig, Calibration};
+use hal::adc::oneshot::{AdcChannelDriver, AdcDriver};
+use hal::adc::{attenuation, ADC1};
+use hal::delay::BLOCK;
+use hal::gpio::{
+    Gpio18, Gpio21, Gpio22, Gpio34, Gpio5, InputPin, Output, OutputPin, Pin, PinDriver, Pull,
+};
+use hal::i2c::{I2cConfig, I2cDriver, I2C0};
 use hal::peripheral::Peripheral;
 use hal::peripherals::Peripherals;
-use std::sync::mpsc::{self, Receiver};
-use std::thread;
-use std::time::{Duration, SystemTime};
+use hal::task::thread::ThreadSpawnConfiguration;
+use hal::units::FromValueType;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::sync::{Mutex, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
 
 // Configuration for WiFi connection
 const SSID: &str = env!("WIFI_SSID");
 const PASSWORD: &str = env!("WIFI_PASS");
 
+// Device name used in logs and as the network hostname. Set DEVICE_NAME at
+// build time to override the default, which is derived from the WiFi MAC.
+const DEVICE_NAME: Option<&str> = option_env!("DEVICE_NAME");
+
+// Run a soft access point named after the device alongside the station, so
+// the device stays reachable when the main network misbehaves. The AP needs
+// a WPA2 password of at least 8 characters, set with SOFTAP_PASSWORD at build time.
+const SOFTAP_ENABLED: bool = false;
+const SOFTAP_PASSWORD: Option<&str> = option_env!("SOFTAP_PASSWORD");
+
 // Time sync interval in seconds
 const NTP_SYNC_INTERVAL: u64 = 3600; // 1 hour
 
+// Once a scheduled sync is this late, SNTP is restarted at this interval
+// until a sync gets through, rather than waiting out another full interval
+const NTP_SYNC_RETRY_INTERVAL_SECS: u64 = 300; // 5 minutes
+
+// Brief rising two-note chime when SNTP sets the clock, so it's audible
+// that the time is right. Skipped if the buzzer is busy at the time.
+const SYNC_CHIME_ENABLED: bool = false;
+
+// Which syncs get the chime, the hourly polls never do on their own
+#[allow(dead_code)]
+enum SyncChimeRepeat {
+    FirstOnly,    // Only the first sync after boot
+    AfterFailure, // Also the first sync after a scheduled one was missed
+}
+
+const SYNC_CHIME_REPEAT: SyncChimeRepeat = SyncChimeRepeat::AfterFailure;
+
+// Recent SNTP syncs and missed syncs kept for the daily summary
+const SYNC_HISTORY_LEN: usize = 32;
+
+// Minimum time between SNTP resyncs triggered by WiFi reconnects
+const NTP_RESYNC_MIN_INTERVAL_SECS: u64 = 60;
+
+// Clock corrections larger than this at sync time are logged as warnings
+const SNTP_OFFSET_WARN_MS: u64 = 2000;
+
+// Forward log lines to a remote syslog server over UDP, in RFC 5424 format.
+// Set SYSLOG_HOST (a hostname or IP address) at build time to enable it.
+// Lines are held while WiFi is down, up to SYSLOG_QUEUE_LEN, and any more
+// are dropped and counted.
+const SYSLOG_HOST: Option<&str> = option_env!("SYSLOG_HOST");
+const SYSLOG_PORT: u16 = 514;
+const SYSLOG_FACILITY: u8 = 16; // local0
+const SYSLOG_QUEUE_LEN: usize = 64;
+const SYSLOG_THREAD_STACK_SIZE: usize = 6144;
+
 // WiFi check interval in milliseconds
 const WIFI_CHECK_INTERVAL: u64 = 30000; // 30 seconds
 
+// Two-letter regulatory country for WiFi channel rules, from WIFI_COUNTRY at
+// build time. Without it, "01" (world safe mode) keeps to channels legal
+// everywhere.
+const WIFI_COUNTRY_CODE: &str = match option_env!("WIFI_COUNTRY") {
+    Some(code) => code,
+    None => "01",
+};
+
+// DHCP lease attempts at boot before carrying on without an IP address.
+// The main loop keeps retrying on each WiFi check after that.
+const DHCP_BOOT_ATTEMPTS: u32 = 3;
+const DHCP_RETRY_DELAY_MS: u64 = 2000;
+
+// Identical repeated warnings are collapsed into one summary per window
+const LOG_REPEAT_WINDOW_SECS: u64 = 300; // 5 minutes
+
+// Low WiFi signal warning, sampled at every WiFi check
+const RSSI_WARNING_ENABLED: bool = true;
+const RSSI_WARNING_THRESHOLD_DBM: i8 = -80;
+const RSSI_LOW_SAMPLES: u32 = 4; // Consecutive weak samples before warning (2 minutes)
+const RSSI_WARNING_INTERVAL_SECS: u64 = 600; // Repeat the warning at most every 10 minutes
+const RSSI_WARNING_REPEATS: u8 = 1;
+const RSSI_WARNING_FREQUENCY: u32 = 1500;
+
+// Battery voltage monitoring on GPIO34, through a resistor divider.
+// Leave disabled on boards without the divider, the pin floats otherwise.
+const BATTERY_MONITOR_ENABLED: bool = false;
+const BATTERY_DIVIDER_RATIO: u32 = 2; // Battery voltage / voltage at the ADC pin
+const BATTERY_LOW_MV: u32 = 3400; // Warn below this battery voltage
+const BATTERY_CHECK_INTERVAL_SECS: u64 = 60;
+const BATTERY_WARNING_INTERVAL_SECS: u64 = 1800; // Repeat the warning at most every 30 minutes
+const BATTERY_WARNING_REPEATS: u8 = 2;
+const BATTERY_WARNING_FREQUENCY: u32 = 1200;
+
+// PIR motion sensor on GPIO35, active high. Alarms can be limited to sound
+// only with someone around, or only with nobody around.
+const PIR_ENABLED: bool = false;
+const PIR_PRESENCE_WINDOW_SECS: u64 = 300; // Motion within this long counts as present
+
+// Who has to be around for an alarm to sound, ignored without the PIR sensor.
+// Present means motion seen within PIR_PRESENCE_WINDOW_SECS.
+const HOUR_CHIME_PRESENCE: Presence = Presence::Any;
+const REMINDER_PRESENCE: Presence = Presence::Any;
+const BEDTIME_PRESENCE: Presence = Presence::Any;
+const QUARTER_CHIME_PRESENCE: Presence = Presence::Any;
+
+// USB/charger presence on GPIO39, fed from VBUS through a divider so it
+// reads high while external power is present. Alarms can be limited to one
+// power source, e.g. quiet as a desk clock on USB, alarming on battery.
+const POWER_SENSE_ENABLED: bool = false;
+
+// Which power source alarms may sound on, ignored without power sensing
+const ALARM_POWER_GATE: PowerGate = PowerGate::Any;
+
+// Any system time before this is treated as "not yet synced"
+const MIN_VALID_EPOCH_SECS: u64 = 1_577_836_800; // 2020-01-01 00:00:00 UTC
+
+// Local time offset from UTC, applied wherever a wall-clock time is shown or
+// alarms are scheduled
+const UTC_OFFSET_SECS: u64 = 8 * 3600; // UTC+8
+
+// What the boot-time wait accepts as a usable clock
+#[allow(dead_code)]
+enum BootSyncPolicy {
+    Completed,             // Only a completed SNTP sync
+    PlausibleAfterTimeout, // Also any plausible clock once the timeout has passed
+}
+
+const BOOT_SYNC_POLICY: BootSyncPolicy = BootSyncPolicy::Completed;
+const BOOT_SYNC_TIMEOUT_SECS: u64 = 120; // Stop waiting and start the main loop after this
+
+// What to do when the clock steps backwards, e.g. after a large NTP
+// correction: SkipRewound keeps the fired-alarm history so rewound alarms
+// don't sound twice, Refire forgets it so they sound again
+const BACKWARD_JUMP_POLICY: BackwardJumpPolicy = BackwardJumpPolicy::SkipRewound;
+const BACKWARD_JUMP_THRESHOLD_SECS: u64 = 60; // Smaller steps are ignored
+
+// Optional DS3231 real-time clock on I2C (SDA GPIO21, SCL GPIO22). It seeds
+// the clock at boot ahead of SNTP and is kept in step with SNTP afterwards.
+const DS3231_ENABLED: bool = false;
+const DS3231_ADDRESS: u8 = 0x68;
+const DS3231_WRITE_INTERVAL_SECS: u64 = 3600; // Write SNTP time back every hour
+
+// Firmware version and the git commit it was built from, reported at boot
+const FIRMWARE_VERSION: &str = env!("CARGO_PKG_VERSION");
+const GIT_HASH: &str = env!("GIT_HASH");
+
+// HEAD commit time (see build.rs), used to seed the clock when running offline
+const BUILD_EPOCH_SECS: &str = env!("BUILD_EPOCH_SECS");
+
+// Overall temperament of the alarms. The profile seeds the defaults below
+// marked as coming from it; set those directly to override a single one.
+#[allow(dead_code)]
+enum Profile {
+    Gentle, // Softened tone edges
+    Normal,
+    Urgent, // More reminder repeats, each one longer than the last
+}
+
+const PROFILE: Profile = Profile::Normal;
+
+struct ProfileDefaults {
+    repeat_growth: bool,
+    tone_ramp: bool,
+    reminder_repeats: u8,
+}
+
+const fn profile_defaults(profile: Profile) -> ProfileDefaults {
+    match profile {
+        Profile::Gentle => ProfileDefaults {
+            repeat_growth: false,
+            tone_ramp: true,
+            reminder_repeats: 1,
+        },
+        Profile::Normal => ProfileDefaults {
+            repeat_growth: false,
+            tone_ramp: false,
+            reminder_repeats: 1,
+        },
+        Profile::Urgent => ProfileDefaults {
+            repeat_growth: true,
+            tone_ramp: false,
+            reminder_repeats: 3,
+        },
+    }
+}
+
+const PROFILE_DEFAULTS: ProfileDefaults = profile_defaults(PROFILE);
+
 // Alarm pattern parameters
-const BEEP_COUNT: u8 = 1; // Changed from 3 to 1
+const BEEP_COUNT: u8 = 1;
 const BEEP_DURATION_MS: u64 = 200;
 const BEEP_PAUSE_MS: u64 = 200;
 const PATTERN_PAUSE_MS: u64 = 500;
 
+// Make alarms more insistent on each repeat: warning patterns gain beeps and
+// scheduled alarms play their melody more times. The hourly chime is left
+// alone, as its repeats count out the hour.
+const REPEAT_GROWTH_ENABLED: bool = PROFILE_DEFAULTS.repeat_growth; // From the profile
+const REPEAT_GROWTH: Option<RepeatGrowth> = if REPEAT_GROWTH_ENABLED {
+    Some(RepeatGrowth {
+        step: 1, // Extra beeps or plays per repeat
+        max: 6,  // Cap on beeps or plays in a single repeat
+    })
+} else {
+    None
+};
+
+// Hard limit on how long a single alarm pattern may keep sounding
+const MAX_ALARM_DURATION_SECS: u64 = 600; // 10 minutes
+
+// Optional soft beep played a few seconds ahead of an alarm, e.g.
+// Some(PreWarning { lead_secs: 30, frequency: 1800, duration_ms: 50 })
+#[allow(dead_code)]
+struct PreWarning {
+    lead_secs: u64,
+    frequency: u32,
+    duration_ms: u64,
+}
+
+const HOUR_CHIME_PREWARNING: Option<PreWarning> = None;
+const REMINDER_PREWARNING: Option<PreWarning> = None;
+
+// Safety limits applied to every tone, regardless of caller
+const MIN_TONE_DURATION_MS: u64 = 10;
+const MAX_TONE_DURATION_MS: u64 = 5000;
+
+// Move every tone into the band the listener hears best. Frequencies are
+// scaled first, which keeps melodies in tune, then clamped to the band.
+const TONE_SCALE_PERCENT: u32 = 100; // e.g. 50 plays everything an octave lower
+const TONE_MIN_FREQUENCY: u32 = 20;
+const TONE_MAX_FREQUENCY: u32 = 20_000;
+
+const _: () = assert!(
+    TONE_MIN_FREQUENCY <= TONE_MAX_FREQUENCY,
+    "the hearing band must not be empty"
+);
+
+// Factory self-test, run at boot when a jumper pulls GPIO4 to GND.
+// The LED on GPIO2 is the on-board LED of most ESP32 DevKit boards.
+const SELF_TEST_FREQUENCIES: [u32; 3] = [1000, 2000, 3000];
+const SELF_TEST_TONE_MS: u64 = 300;
+
+// Kind of buzzer on GPIO5. Passive buzzers need a square wave at the tone
+// frequency; active ones have their own oscillator and are only switched on.
+#[allow(dead_code)]
+enum BuzzerType {
+    Passive,
+    Active,
+}
+
+const BUZZER_TYPE: BuzzerType = BuzzerType::Passive;
+
+// Soften the click some piezo buzzers make at tone edges by ramping the duty
+// cycle up at the start of each tone and back down at the end. The ramps are
+// added around the tone, so each tone lasts 2 * TONE_RAMP_MS longer.
+const TONE_RAMP_ENABLED: bool = PROFILE_DEFAULTS.tone_ramp; // From the profile
+const TONE_RAMP_MS: u64 = 5;
+
+// Threshold below which we'll use a spin loop instead of sleep.
+// FreeRTOS can't sleep for less than one tick, so this is the tick period
+// from CONFIG_FREERTOS_HZ. sdkconfig.defaults keeps the 100 Hz default, a
+// 10ms tick, so every tone above 50 Hz is spun; with CONFIG_FREERTOS_HZ=1000
+// it drops to 1ms and only tones above 500 Hz are.
+const MIN_SLEEP_THRESHOLD_US: u64 = tick_period_us(esp_idf_svc::sys::configTICK_RATE_HZ);
+
+// How a long spun tone gives other tasks a turn on the core. The buzzer
+// thread runs above most tasks, so a bare yield only lets those of equal or
+// higher priority in. Sleeping a tick also lets the idle task feed the task
+// watchdog, but the tone goes silent for that tick: 10ms in every
+// SPIN_YIELD_INTERVAL_MS at the 100 Hz sdkconfig.defaults ships, which is
+// audible. Only pick Tick after setting CONFIG_FREERTOS_HZ=1000 (1ms gaps).
+// A single note is far shorter than the task watchdog timeout, so the bare
+// yield is enough by default.
+#[allow(dead_code)]
+enum SpinYield {
+    Never,
+    Yield, // thread::yield_now
+    Tick,  // Sleep for one FreeRTOS tick
+}
+
+const SPIN_YIELD: SpinYield = SpinYield::Yield;
+
+// Morse code playback parameters
+const MORSE_FREQUENCY: u32 = 2000;
+const MORSE_WPM: u64 = 15; // Words per minute, "PARIS" timing
+const MORSE_MAX_CHARS: usize = 32; // Longer messages are cut short
+
+// Optional Morse message sounded once startup has finished, e.g. Some("OK")
+const BOOT_MORSE_MESSAGE: Option<&str> = None;
+
+// Holding the BOOT button (GPIO0) beeps out the last octet of the IP
+// address in Morse, for finding a headless device on the network
+const IP_ANNOUNCE_ENABLED: bool = false;
+const IP_ANNOUNCE_HOLD_MS: u128 = 2000;
+
+// A shorter press of the same button sounds the current local time. The
+// button is polled with the main loop, so hold it for about half a second.
+const TIME_ANNOUNCE_ENABLED: bool = false;
+
+// Minimum time between announcements triggered from the button, so mashing
+// it can't pile up queued tones. Scheduled alarms are not affected.
+const MANUAL_TRIGGER_COOLDOWN_SECS: u64 = 3;
+
+// How the time is sounded on demand
+#[allow(dead_code)]
+enum TimeAnnounceEncoding {
+    Morse,    // Hours and minutes as digits, e.g. "7 05"
+    Repeater, // Low beeps for hours, two-tone for quarters, high for minutes
+}
+
+const TIME_ANNOUNCE_ENCODING: TimeAnnounceEncoding = TimeAnnounceEncoding::Repeater;
+
+// Pause between consecutive notes of a melody
+const NOTE_PAUSE_MS: u64 = 50;
+
+// Two-note descending chime for the top of the hour, repeated once per hour
+const HOUR_CHIME_MELODY: &[Note] = &[
+    Note {
+        frequency: 2300,
+        duration_ms: 200,
+    },
+    Note {
+        frequency: 1800,
+        duration_ms: 300,
+    },
+];
+
+// Single short blip for the reminder at ten past
+const REMINDER_MELODY: &[Note] = &[Note {
+    frequency: 2800,
+    duration_ms: 80,
+}];
+const REMINDER_REPEATS: u8 = PROFILE_DEFAULTS.reminder_repeats; // From the profile
+
+// The reminder follows the hourly chime by this many minutes, and moves with
+// HOUR_CHIME_MINUTE. It must stay within the hour the chime belongs to.
+const REMINDER_OFFSET_MINS: u64 = 10;
+const REMINDER_MINUTE: u64 = HOUR_CHIME_MINUTE + REMINDER_OFFSET_MINS;
+
+const _: () = assert!(
+    REMINDER_MINUTE < 60,
+    "the reminder must fall within the hour"
+);
+
+// Quick high double-blip for test beeps, distinct from every real alarm
+const TEST_BEEP_MELODY: &[Note] = &[
+    Note {
+        frequency: 3500,
+        duration_ms: 40,
+    },
+    Note {
+        frequency: 3500,
+        duration_ms: 40,
+    },
+];
+
+// Rising two-note chime confirming an SNTP sync
+const SYNC_CHIME_MELODY: &[Note] = &[
+    Note {
+        frequency: 1500,
+        duration_ms: 60,
+    },
+    Note {
+        frequency: 2500,
+        duration_ms: 60,
+    },
+];
+
+// Minute-repeater tones for the time announcement
+const REPEATER_HOUR_MELODY: &[Note] = &[Note {
+    frequency: 1000,
+    duration_ms: 150,
+}];
+const REPEATER_QUARTER_MELODY: &[Note] = &[
+    Note {
+        frequency: 2500,
+        duration_ms: 150,
+    },
+    Note {
+        frequency: 1000,
+        duration_ms: 150,
+    },
+];
+const REPEATER_MINUTE_MELODY: &[Note] = &[Note {
+    frequency: 2500,
+    duration_ms: 150,
+}];
+
+// Wind-down chime at bedtime: a soft descending melody played once a day
+const BEDTIME_CHIME_ENABLED: bool = false;
+const BEDTIME_HOUR: u64 = 22;
+const BEDTIME_MINUTE: u64 = 30;
+const BEDTIME_MELODY: &[Note] = &[
+    Note {
+        frequency: 1568,
+        duration_ms: 400,
+    },
+    Note {
+        frequency: 1319,
+        duration_ms: 400,
+    },
+    Note {
+        frequency: 1047,
+        duration_ms: 400,
+    },
+    Note {
+        frequency: 784,
+        duration_ms: 800,
+    },
+];
+
+// Westminster quarter chimes: the four changes of the Cambridge Quarters
+// at :15, :30 and :45, and the full sequence followed by one strike per hour
+// on the hour in place of the normal hour chime
+const WESTMINSTER_CHIME_ENABLED: bool = false;
+const WESTMINSTER_GS: u32 = 1661; // G#6
+const WESTMINSTER_FS: u32 = 1480; // F#6
+const WESTMINSTER_E: u32 = 1319; // E6
+const WESTMINSTER_B: u32 = 988; // B5
+const WESTMINSTER_NOTE_MS: u64 = 400;
+const WESTMINSTER_LONG_MS: u64 = 800; // Last note of each change
+
+// Shorthand for the longer Westminster melodies
+const fn note(frequency: u32, duration_ms: u64) -> Note {
+    Note {
+        frequency,
+        duration_ms,
+    }
+}
+
+// First quarter: one change
+const WESTMINSTER_QUARTER_PAST: &[Note] = &[
+    // Change 1
+    note(WESTMINSTER_GS, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_FS, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_E, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_B, WESTMINSTER_LONG_MS),
+];
+
+// Half hour: two changes
+const WESTMINSTER_HALF_PAST: &[Note] = &[
+    // Change 2
+    note(WESTMINSTER_E, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_GS, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_FS, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_B, WESTMINSTER_LONG_MS),
+    // Change 3
+    note(WESTMINSTER_E, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_FS, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_GS, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_E, WESTMINSTER_LONG_MS),
+];
+
+// Third quarter: three changes
+const WESTMINSTER_QUARTER_TO: &[Note] = &[
+    // Change 4
+    note(WESTMINSTER_GS, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_E, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_FS, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_B, WESTMINSTER_LONG_MS),
+    // Change 5
+    note(WESTMINSTER_B, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_FS, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_GS, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_E, WESTMINSTER_LONG_MS),
+    // Change 1
+    note(WESTMINSTER_GS, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_FS, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_E, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_B, WESTMINSTER_LONG_MS),
+];
+
+// Full hour: four changes, followed by the hour strike
+const WESTMINSTER_HOUR: &[Note] = &[
+    // Change 2
+    note(WESTMINSTER_E, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_GS, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_FS, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_B, WESTMINSTER_LONG_MS),
+    // Change 3
+    note(WESTMINSTER_E, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_FS, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_GS, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_E, WESTMINSTER_LONG_MS),
+    // Change 4
+    note(WESTMINSTER_GS, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_E, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_FS, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_B, WESTMINSTER_LONG_MS),
+    // Change 5
+    note(WESTMINSTER_B, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_FS, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_GS, WESTMINSTER_NOTE_MS),
+    note(WESTMINSTER_E, WESTMINSTER_LONG_MS),
+];
+
+// Low bell struck once per hour after the full sequence
+const WESTMINSTER_STRIKE: &[Note] = &[note(WESTMINSTER_B, 900)];
+
+// Registry of the built-in melodies, the one place names are defined. Every
+// melody is played by looking up its name here.
+const MELODIES: &[MelodyInfo] = &[
+    MelodyInfo {
+        name: "hour-chime",
+        description: "Two-note descending chime",
+        notes: HOUR_CHIME_MELODY,
+    },
+    MelodyInfo {
+        name: "reminder-blip",
+        description: "Single short blip",
+        notes: REMINDER_MELODY,
+    },
+    MelodyInfo {
+        name: "bedtime",
+        description: "Soft four-note descending wind-down",
+        notes: BEDTIME_MELODY,
+    },
+    MelodyInfo {
+        name: "westminster-quarter-past",
+        description: "First Westminster change",
+        notes: WESTMINSTER_QUARTER_PAST,
+    },
+    MelodyInfo {
+        name: "westminster-half-past",
+        description: "Two Westminster changes",
+        notes: WESTMINSTER_HALF_PAST,
+    },
+    MelodyInfo {
+        name: "westminster-quarter-to",
+        description: "Three Westminster changes",
+        notes: WESTMINSTER_QUARTER_TO,
+    },
+    MelodyInfo {
+        name: "westminster-hour",
+        description: "Full Westminster Quarters sequence",
+        notes: WESTMINSTER_HOUR,
+    },
+    MelodyInfo {
+        name: "westminster-strike",
+        description: "Single low bell strike",
+        notes: WESTMINSTER_STRIKE,
+    },
+    MelodyInfo {
+        name: "test-beep",
+        description: "Quick high double-blip",
+        notes: TEST_BEEP_MELODY,
+    },
+    MelodyInfo {
+        name: "sync-confirm",
+        description: "Short rising two-note chime",
+        notes: SYNC_CHIME_MELODY,
+    },
+    MelodyInfo {
+        name: "repeater-hour",
+        description: "Low repeater tone counting hours",
+        notes: REPEATER_HOUR_MELODY,
+    },
+    MelodyInfo {
+        name: "repeater-quarter",
+        description: "Two-tone repeater ding-dong counting quarters",
+        notes: REPEATER_QUARTER_MELODY,
+    },
+    MelodyInfo {
+        name: "repeater-minute",
+        description: "High repeater tone counting minutes",
+        notes: REPEATER_MINUTE_MELODY,
+    },
+];
+
+// Played in place of a melody whose name is not in the registry
+const FALLBACK_MELODY: &[Note] = HOUR_CHIME_MELODY;
+
+// Melody played by each alarm, by registry name
+const HOUR_CHIME_MELODY_NAME: &str = "hour-chime";
+const REMINDER_MELODY_NAME: &str = "reminder-blip";
+const BEDTIME_MELODY_NAME: &str = "bedtime";
+const SYNC_CHIME_MELODY_NAME: &str = "sync-confirm";
+const TEST_BEEP_MELODY_NAME: &str = "test-beep";
+const HOUR_STRIKE_MELODY_NAME: &str = "westminster-strike"; // With the Westminster chime
+const WESTMINSTER_MELODY_NAMES: [&str; 4] = [
+    "westminster-hour", // On the hour
+    "westminster-quarter-past",
+    "westminster-half-past",
+    "westminster-quarter-to",
+];
+const REPEATER_HOUR_MELODY_NAME: &str = "repeater-hour";
+const REPEATER_QUARTER_MELODY_NAME: &str = "repeater-quarter";
+const REPEATER_MINUTE_MELODY_NAME: &str = "repeater-minute";
+
+// Which outputs an alarm drives. The vibration motor sits on GPIO18
+// behind a transistor.
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+enum AlarmOutput {
+    Buzzer,
+    Vibration,
+    Both,
+}
+
+impl AlarmOutput {
+    fn buzzer(self) -> bool {
+        matches!(self, AlarmOutput::Buzzer | AlarmOutput::Both)
+    }
+
+    fn vibration(self) -> bool {
+        matches!(self, AlarmOutput::Vibration | AlarmOutput::Both)
+    }
+}
+
+const HOUR_CHIME_OUTPUT: AlarmOutput = AlarmOutput::Buzzer;
+const REMINDER_OUTPUT: AlarmOutput = AlarmOutput::Buzzer;
+
+// Vibration motor pulse pattern
+const VIBRATION_PULSE_MS: u64 = 400;
+const VIBRATION_PAUSE_MS: u64 = 300;
+
+// Minute past the hour the hourly chime sounds at
+const HOUR_CHIME_MINUTE: u64 = 0;
+
+// Upper bound on the number of hourly chime repeats
+const HOUR_CHIME_MAX_REPEATS: u8 = 12;
+
+// How many beeps the hourly chime gives at midnight: Silent, Twelve or
+// TwentyFour (still limited by HOUR_CHIME_MAX_REPEATS)
+const HOUR_CHIME_MIDNIGHT: MidnightChime = MidnightChime::Twelve;
+const HOUR_CHIME_NOON_ENABLED: bool = true; // Whether noon chimes 12 or stays silent
+
+// When each alarm comes due, from the settings above
+const SCHEDULE: Schedule = Schedule {
+    hour_chime_minute: HOUR_CHIME_MINUTE,
+    reminder_minute: REMINDER_MINUTE,
+    bedtime: if BEDTIME_CHIME_ENABLED {
+        Some(BEDTIME_HOUR * 60 + BEDTIME_MINUTE)
+    } else {
+        None
+    },
+    quarter_chime: WESTMINSTER_CHIME_ENABLED,
+};
+
+// What to do when several alarms come due in the same minute
+const ALARM_COLLISION_POLICY: CollisionPolicy = CollisionPolicy::PlayAll;
+
+// Alarm types permitted from start_hour up to (not including) end_hour.
+// Ranges may wrap past midnight, e.g. 22 to 7. The first rule covering the
+// current hour decides; uncovered hours are quiet.
+// For example, a rule { 0, 7, false, true, false, false } would keep just the
+// reminder blip overnight.
+const ALARM_POLICY: &[PolicyRule] = &[PolicyRule {
+    start_hour: 7,
+    end_hour: 24,
+    hour_chime: true,
+    reminder: true,
+    bedtime: true,
+    quarter_chime: true,
+}];
+
+// Dates (year, month, day) on which all alarms are skipped, year 0 matches
+// every year. For example, &[(0, 1, 1)] skips New Year's Day.
+const ALARM_SKIP_DATES: &[(u32, u32, u32)] = &[];
+
+const POLICY: Policy = Policy {
+    rules: ALARM_POLICY,
+    skip_dates: ALARM_SKIP_DATES,
+    presence: alarm_presence,
+    power_gate: ALARM_POWER_GATE,
+};
+
+// Everything the scheduler needs to decide which alarms sound
+const ALARMS: AlarmConfig = AlarmConfig {
+    schedule: SCHEDULE,
+    policy: POLICY,
+    collisions: ALARM_COLLISION_POLICY,
+    midnight_chime: HOUR_CHIME_MIDNIGHT,
+    noon_chime: HOUR_CHIME_NOON_ENABLED,
+    max_chime_repeats: HOUR_CHIME_MAX_REPEATS,
+    reminder_repeats: REMINDER_REPEATS,
+};
+
 const DEBUG_ON: bool = false;
 
+// What goes to the serial console: human-readable logs, machine-readable
+// JSON event lines for a host-side logger, or both
+#[allow(dead_code)]
+#[derive(PartialEq)]
+enum SerialOutput {
+    Logs,
+    LogsAndEvents,
+    EventsOnly,
+}
+
+const SERIAL_OUTPUT: SerialOutput = SerialOutput::Logs;
+
+// Local time of the daily health summary in the log
+const DAILY_SUMMARY_ENABLED: bool = true;
+const DAILY_SUMMARY_HOUR: u64 = 23;
+const DAILY_SUMMARY_MINUTE: u64 = 59;
+
+// Optional daily reboot to clear slow leaks or accumulated state. It waits
+// while the buzzer is busy or an alarm is about to sound, for up to
+// DAILY_REBOOT_WINDOW_MINS.
+const DAILY_REBOOT_ENABLED: bool = false;
+const DAILY_REBOOT_HOUR: u64 = 4;
+const DAILY_REBOOT_MINUTE: u64 = 0;
+const DAILY_REBOOT_WINDOW_MINS: u64 = 30;
+const DAILY_REBOOT_NOTICE_MINS: u64 = 5; // Log the planned reboot this far ahead
+const DAILY_REBOOT_MIN_UPTIME_SECS: u64 = 3600; // Never reboot again straight after booting
+const DAILY_REBOOT_ALARM_GUARD_MINS: u64 = 2; // Hold off while an alarm is due this soon
+
+// ESP32 strapping pins, sampled at reset to select the boot mode and flash
+// voltage. A buzzer or motor driver pulling one of these at reset can stop
+// the board from booting, so warn when an output sits on one.
+const STRAPPING_PIN_CHECK_ENABLED: bool = true;
+const STRAPPING_PINS: &[i32] = &[0, 2, 5, 12, 15];
+
+// Last-resort pattern beeped on GPIO5 when core initialization fails
+const BOOT_FAILURE_BEEPS: u8 = 5;
+const BOOT_FAILURE_FREQUENCY: u32 = 1000;
+const BOOT_FAILURE_BEEP_MS: u64 = 500;
+
+// Speed-up factor for the simulated clock, only compiled in with the
+// "simulated-clock" feature for exercising schedules on real hardware
+#[cfg(feature = "simulated-clock")]
+const SIMULATED_CLOCK_SPEED: u64 = 60;
+
+#[cfg(all(feature = "simulated-clock", not(debug_assertions)))]
+compile_error!("the simulated-clock feature is for test builds only");
+
+// Buzzer thread FreeRTOS settings. Priority 10 sits above the main task (1)
+// and default pthreads (5) but below the WiFi and lwIP tasks (18+), which
+// keeps tone timing steady without starving networking. 8 KB of stack
+// leaves room for logging and formatting Morse messages.
+const BUZZER_THREAD_PRIORITY: u8 = 10;
+const BUZZER_THREAD_STACK_SIZE: usize = 8192;
+
+// High tones are spun rather than slept, which holds the core. At the
+// priority above, give other tasks a turn this often while spinning (see
+// SPIN_YIELD for how).
+const SPIN_YIELD_INTERVAL_MS: u64 = 100;
+
+// Time before restarting a dead buzzer thread, doubling after each restart.
+// A thread that keeps dying, e.g. because its pins won't initialize, is
+// given up on after BUZZER_RESTART_MAX_ATTEMPTS and the clock runs silent.
+const BUZZER_RESTART_INTERVAL_SECS: u64 = 5;
+const BUZZER_RESTART_MAX_ATTEMPTS: u32 = 5;
+
+// Demo mode: on the first boot with it enabled, play each alarm type in turn
+// a few seconds apart, ignoring the schedule and policy, then carry on as
+// normal. A "demo_done" flag in NVS keeps later boots quiet; erase NVS to
+// see it again.
+const DEMO_SCHEDULE_ENABLED: bool = false;
+const DEMO_SPACING_SECS: u64 = 5;
+
+// Evaluate and log alarms as usual but never sound the buzzer
+const SILENT_MODE: bool = false;
+
+// Buzzer messages sent but not yet finished playing
+static BUZZER_PENDING: AtomicU32 = AtomicU32::new(0);
+
+// SNTP syncs since the last daily summary, counted from the SNTP callback
+static SNTP_SYNC_COUNT: AtomicU32 = AtomicU32::new(0);
+
+// When the last SNTP sync arrived, set from the SNTP callback
+static LAST_SNTP_SYNC: Mutex<Option<Instant>> = Mutex::new(None);
+
+// Recent SNTP syncs, oldest overwritten first
+static SYNC_HISTORY: Mutex<heapless::HistoryBuffer<SyncRecord, SYNC_HISTORY_LEN>> =
+    Mutex::new(heapless::HistoryBuffer::new());
+
+// One entry in the SNTP sync history
+#[derive(Clone, Copy)]
+struct SyncRecord {
+    timestamp: u64,         // Seconds since the epoch
+    offset_ms: Option<i64>, // Correction applied, None for the first sync
+    synced: bool,           // False for a scheduled sync that was missed
+}
+
+// Logger for the whole firmware: the ESP-IDF console, plus syslog
+// forwarding once it is running
+static LOGGER: ForwardingLogger = ForwardingLogger {
+    console: EspLogger::new(),
+    syslog: OnceLock::new(),
+};
+
+// Whether the network is up for syslog forwarding, set from the main loop
+static SYSLOG_ONLINE: AtomicBool = AtomicBool::new(false);
+
+// Log lines dropped because the syslog queue was full
+static SYSLOG_DROPPED: AtomicU32 = AtomicU32::new(0);
+
+// Counters for the daily summary, reset after each one is logged
+#[derive(Default)]
+struct DailyStats {
+    alarms_fired: u32,
+    alarms_suppressed: u32,
+    wifi_reconnects: u32,
+    rssi_min: Option<i8>,
+    rssi_max: Option<i8>,
+}
+
+impl DailyStats {
+    fn record_rssi(&mut self, rssi: i8) {
+        self.rssi_min = Some(self.rssi_min.map_or(rssi, |min| min.min(rssi)));
+        self.rssi_max = Some(self.rssi_max.map_or(rssi, |max| max.max(rssi)));
+    }
+
+    // Log the summary and start counting afresh
+    fn log_and_reset(&mut self, uptime: Duration) {
+        let rssi = match (self.rssi_min, self.rssi_max) {
+            (Some(min), Some(max)) => format!("{} to {} dBm", min, max),
+            _ => "n/a".to_string(),
+        };
+        log::info!(
+            "Daily summary: {} alarms, {} suppressed, {} WiFi reconnects, {} SNTP syncs, RSSI {}, uptime {}",
+            self.alarms_fired,
+            self.alarms_suppressed,
+            self.wifi_reconnects,
+            SNTP_SYNC_COUNT.swap(0, Ordering::Relaxed),
+            rssi,
+            format_uptime(uptime)
+        );
+        log_sync_history();
+        *self = Self::default();
+    }
+}
+
+// Collapses repeats of the same log message into a periodic summary
+struct LogDeduplicator {
+    last_message: String,
+    last_logged: Option<Instant>,
+    level: log::Level,
+    repeats: u32,
+}
+
+impl LogDeduplicator {
+    fn new() -> Self {
+        Self {
+            last_message: String::new(),
+            last_logged: None,
+            level: log::Level::Info,
+            repeats: 0,
+        }
+    }
+
+    // Log the message unless it repeats the last one within the window
+    fn log(&mut self, level: log::Level, message: String) {
+        let in_window = self
+            .last_logged
+            .is_some_and(|t| t.elapsed().as_secs() < LOG_REPEAT_WINDOW_SECS);
+        if message == self.last_message && in_window {
+            self.repeats += 1;
+            return;
+        }
+
+        if message == self.last_message && self.repeats > 0 {
+            log::log!(
+                level,
+                "{} (repeated {} times in last {} min)",
+                message,
+                self.repeats + 1,
+                LOG_REPEAT_WINDOW_SECS / 60
+            );
+        } else {
+            self.flush();
+            log::log!(level, "{}", message);
+        }
+
+        self.last_message = message;
+        self.last_logged = Some(Instant::now());
+        self.level = level;
+        self.repeats = 0;
+    }
+
+    // Report any repeats that haven't been logged yet
+    fn flush(&mut self) {
+        if self.repeats > 0 {
+            log::log!(
+                self.level,
+                "{} (repeated {} more times)",
+                self.last_message,
+                self.repeats
+            );
+        }
+        self.repeats = 0;
+        self.last_message.clear();
+        self.last_logged = None;
+    }
+}
+
+// Writes log lines to the console and queues them for the syslog thread.
+// Sending never happens on the logging thread, as some callers (the SNTP
+// callback) run inside the network stack.
+struct ForwardingLogger {
+    console: EspLogger,
+    syslog: OnceLock<SyncSender<(log::Level, String)>>,
+}
+
+impl log::Log for ForwardingLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.console.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        self.console.log(record);
+
+        let Some(syslog) = self.syslog.get() else {
+            return;
+        };
+        if self.enabled(record.metadata())
+            && syslog
+                .try_send((record.level(), record.args().to_string()))
+                .is_err()
+        {
+            SYSLOG_DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn flush(&self) {
+        self.console.flush();
+    }
+}
+
+// An alarm that has come due and is waiting to be sounded
+struct FiredAlarm {
+    alarm_type: AlarmType,
+    output: AlarmOutput,
+    notes: &'static [Note],
+    repeat_count: u8,
+}
+
+impl FiredAlarm {
+    // Send the melody and/or vibration for this alarm to the buzzer thread
+    fn send(&self, buzzer_tx: &BuzzerChannel) {
+        if self.output.buzzer() {
+            send_to_buzzer(
+                buzzer_tx,
+                BuzzerMessage::PlayMelody {
+                    notes: self.notes,
+                    repeat_count: self.repeat_count,
+                    growth: match self.alarm_type {
+                        AlarmType::HourChime => None,
+                        _ => REPEAT_GROWTH,
+                    },
+                },
+                self.alarm_type.name(),
+            );
+        }
+        if self.output.vibration() {
+            send_to_buzzer(
+                buzzer_tx,
+                BuzzerMessage::Vibrate {
+                    pulses: self.repeat_count,
+                },
+                &format!("{} vibration", self.alarm_type.name()),
+            );
+        }
+    }
+}
+
+// Events reported as JSON lines on the serial console
+enum Event<'a> {
+    AlarmFired { alarm: &'a str },
+    AlarmSuppressed { alarm: &'a str, reason: &'a str },
+    WifiDisconnected,
+    WifiReconnected { ip: String },
+    TimeSynced { offset_ms: Option<i64> },
+}
+
 // Message types for buzzer control - updated with parameters
 enum BuzzerMessage {
-    PlayAlarm { repeat_count: u8, frequency: u32 },
+    PlayAlarm {
+        repeat_count: u8,
+        frequency: u32,
+    },
+    PlayMorse {
+        text: String,
+    },
+    PlayTone {
+        frequency: u32,
+        duration_ms: u64,
+    },
+    PlayMelody {
+        notes: &'static [Note],
+        repeat_count: u8,
+        growth: Option<RepeatGrowth>,
+    },
+    Vibrate {
+        pulses: u8,
+    },
+    TestBeep,
 }
 
 fn main() -> Result<()> {
+    let boot_time = Instant::now();
+
     // Initialize ESP-IDF
     esp_idf_svc::sys::link_patches();
-    esp_idf_svc::log::EspLogger::initialize_default();
+    if log::set_logger(&LOGGER).is_ok() {
+        LOGGER.console.initialize();
+    }
+    if SERIAL_OUTPUT == SerialOutput::EventsOnly {
+        log::set_max_level(log::LevelFilter::Off);
+    }
 
-    log::info!("ESP32 Alarm Clock starting...");
+    let device_name = device_name();
+    if let Some(host) = SYSLOG_HOST {
+        if let Err(e) = start_syslog(host, &device_name) {
+            log::warn!("Syslog forwarding unavailable: {:?}", e);
+        }
+    }
+    log::info!("ESP32 Alarm Clock '{}' starting...", device_name);
+    log_version_info();
+    for melody in MELODIES {
+        log::debug!("Melody '{}': {}", melody.name, melody.description);
+    }
+
+    #[cfg(feature = "simulated-clock")]
+    log::warn!(
+        "TEST MODE: alarms follow a simulated clock running {}x fast",
+        SIMULATED_CLOCK_SPEED
+    );
 
     // Get access to the peripherals
-    let peripherals = Peripherals::take()?;
+    // This only fails when the peripherals are already taken, so GPIO5 is
+    // owned elsewhere and the failure can't be beeped out
+    let mut peripherals = Peripherals::take().context("Failed to take peripherals")?;
 
     // Get the system event loop
-    let sysloop = EspSystemEventLoop::take()?;
+    let sysloop = match EspSystemEventLoop::take().context("Failed to take system event loop") {
+        Ok(sysloop) => sysloop,
+        Err(e) => {
+            signal_boot_failure(&mut peripherals.pins.gpio5);
+            return Err(e);
+        }
+    };
+
+    if STRAPPING_PIN_CHECK_ENABLED {
+        warn_if_strapping_pin("Buzzer", peripherals.pins.gpio5.pin());
+        warn_if_strapping_pin("Vibration motor", peripherals.pins.gpio18.pin());
+    }
+
+    let nvs = EspDefaultNvsPartition::take().context("Failed to take NVS partition")?;
+
+    // Count boots to spot reboot loops in the field
+    match increment_boot_count(nvs.clone()) {
+        Ok(count) => log::info!("Boot number {}", count),
+        Err(e) => log::warn!("Failed to update boot counter: {:?}", e),
+    }
+
+    let run_demo = DEMO_SCHEDULE_ENABLED
+        && match claim_demo_run(nvs.clone()) {
+            Ok(first_run) => first_run,
+            Err(e) => {
+                log::warn!("Failed to read the demo flag, skipping the demo: {:?}", e);
+                false
+            }
+        };
+
+    // Run the hardware self-test if the diagnostics jumper is fitted. It must
+    // never stop the clock booting, so failures are only logged.
+    match self_test_requested(&mut peripherals.pins.gpio4) {
+        Ok(true) => {
+            let pins = &mut peripherals.pins;
+            if let Err(e) = run_self_test(&mut pins.gpio5, &mut pins.gpio2) {
+                log::error!("Self-test failed: {:?}", e);
+            }
+        }
+        Ok(false) => {}
+        Err(e) => log::error!("Failed to read self-test pin, skipping self-test: {:?}", e),
+    }
 
     // Setup buzzer control channel and thread
-    let (buzzer_tx, buzzer_rx) = mpsc::channel();
+    let (mut buzzer_tx, buzzer_thread) =
+        spawn_buzzer_thread(peripherals.pins.gpio5, peripherals.pins.gpio18)
+            .context("Failed to start buzzer thread")?;
+    let mut buzzer_thread = Some(buzzer_thread); // None once a restart has failed
+    let mut last_buzzer_restart: Option<Instant> = None;
+    let mut buzzer_restarts: u32 = 0;
 
-    // Start buzzer control thread
-    thread::spawn(move || {
-        let pin = peripherals.pins.gpio5;
-        if let Ok(mut buzzer) = PinDriver::output(pin) {
-            buzzer_control_task(buzzer_rx, &mut buzzer);
-        } else {
-            log::error!("Failed to initialize buzzer pin!");
+    // Seed the clock from the external RTC, if one is fitted
+    let mut rtc = if DS3231_ENABLED {
+        match start_ds3231(
+            peripherals.i2c0,
+            peripherals.pins.gpio21,
+            peripherals.pins.gpio22,
+        ) {
+            Ok(rtc) => Some(rtc),
+            Err(e) => {
+                log::warn!("DS3231 unavailable, using the internal clock: {:?}", e);
+                None
+            }
         }
-    });
+    } else {
+        None
+    };
+    let mut last_rtc_write: Option<Instant> = None;
+
+    // Button for the IP address and time announcements, active low with a pull-up
+    let announce_button = if IP_ANNOUNCE_ENABLED || TIME_ANNOUNCE_ENABLED {
+        let mut button = PinDriver::input(peripherals.pins.gpio0)
+            .context("Failed to initialize announce button")?;
+        button.set_pull(Pull::Up)?;
+        Some(button)
+    } else {
+        None
+    };
+    let mut button_pressed_at: Option<Instant> = None;
+    let mut ip_announced = false; // Whether this press has been handled
+    let mut last_manual_trigger: Option<Instant> = None;
+
+    // Set up battery monitoring, carrying on without it if the ADC fails
+    let mut battery = if BATTERY_MONITOR_ENABLED {
+        match start_battery_monitor(peripherals.adc1, peripherals.pins.gpio34) {
+            Ok(channel) => Some(channel),
+            Err(e) => {
+                log::warn!("Battery monitoring unavailable: {:?}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let mut last_battery_check: Option<Instant> = None;
+
+    // PIR motion sensor, its output is driven so no pull is needed
+    let pir = if PIR_ENABLED {
+        Some(PinDriver::input(peripherals.pins.gpio35).context("Failed to initialize PIR sensor")?)
+    } else {
+        None
+    };
+    let mut last_motion: Option<Instant> = None;
+    let mut last_battery_warning: Option<Instant> = None;
+
+    // USB power sense, driven by the divider so no pull is needed
+    let power_sense = if POWER_SENSE_ENABLED {
+        Some(
+            PinDriver::input(peripherals.pins.gpio39)
+                .context("Failed to initialize power sense")?,
+        )
+    } else {
+        None
+    };
+    let mut power_source: Option<PowerSource> = None;
 
     // Connect to WiFi
     log::info!("Connecting to WiFi network '{}'...", SSID);
-    let mut wifi = connect_wifi(peripherals.modem, sysloop.clone(), SSID, PASSWORD)?;
+    let mut wifi = start_wifi(
+        peripherals.modem,
+        sysloop.clone(),
+        nvs,
+        SSID,
+        PASSWORD,
+        &device_name,
+    )
+    .context("Failed to start WiFi driver")?;
+    let online = match connect_wifi(&mut wifi) {
+        Ok(()) => true,
+        Err(e) => {
+            log::warn!("WiFi unavailable ({:?}), running in offline mode", e);
+            false
+        }
+    };
+    SYSLOG_ONLINE.store(online, Ordering::Relaxed);
+
+    // Advertise the device as <device name>.local
+    let mut mdns = match start_mdns(&device_name) {
+        Ok(mdns) => Some(mdns),
+        Err(e) => {
+            log::warn!("Failed to start mDNS: {:?}", e);
+            None
+        }
+    };
 
     // Configure SNTP for time synchronization
     log::info!("Setting up SNTP service...");
-    let sntp = setup_sntp()?;
+    let sntp = setup_sntp().context("Failed to set up SNTP")?;
 
-    // Wait for initial time synchronization
-    log::info!("Waiting for initial time sync...");
-    while sntp.get_sync_status() != SyncStatus::Completed {
-        thread::sleep(Duration::from_millis(500));
+    if online {
+        // Wait for initial time synchronization
+        log::info!("Waiting for initial time sync...");
+        if !wait_for_boot_sync(&sntp) {
+            log::warn!("Starting without a usable time, alarms wait for SNTP to sync");
+        }
+    } else {
+        // Run as a standalone clock until WiFi comes back and SNTP syncs
+        seed_clock_from_build_time();
+    }
+
+    if let Some(text) = BOOT_MORSE_MESSAGE {
+        send_to_buzzer(
+            &buzzer_tx,
+            BuzzerMessage::PlayMorse {
+                text: text.to_string(),
+            },
+            "boot message",
+        );
     }
-    log::info!("Initial time sync complete");
 
-    let mut last_hour = -1;
-    let mut last_10_min_alarm = -1;
+    // Due time (minutes since the epoch) each alarm last fired for
+    let mut last_fired: HashMap<Trigger, u64> = HashMap::new();
+    // After a planned restart, such as the daily reboot, the alarms already
+    // due this hour sounded before it. They are marked fired on the first
+    // valid clock reading rather than sounding again.
+    let mut seed_last_fired = unsafe { esp_idf_svc::sys::esp_reset_reason() }
+        == esp_idf_svc::sys::esp_reset_reason_t_ESP_RST_SW;
+    let mut last_clock_secs: Option<u64> = None; // Alarm clock reading on the last pass
     let mut last_wifi_check = SystemTime::now();
+    let mut last_ntp_resync: Option<Instant> = None;
+    let mut sntp_retrying = false; // Whether a scheduled sync has been missed
+    let mut last_seen_sync: Option<Instant> = None; // SNTP sync last checked for the chime
+    let mut sync_chime_due = SYNC_CHIME_ENABLED; // Chime on the next sync seen
+    let mut wifi_lost_log = LogDeduplicator::new();
+    let mut wifi_connect_log = LogDeduplicator::new();
+    let mut wifi_dhcp_log = LogDeduplicator::new();
+    let mut wifi_down = false; // Whether the loss of WiFi has been reported
     let mut last_log_time: i64 = -1; // Track the last time we logged
+    let mut alarms_suppressed = false; // Whether alarms are held back pending a valid time
+    let mut last_skip_day: u64 = 0; // Last day we logged as an alarm skip date
+    let mut daily_stats = DailyStats::default();
+    let mut last_summary_day: u64 = 0; // Day the daily summary was last logged
+    let mut last_reboot_notice_day: u64 = 0; // Day the planned reboot was last announced
+    let mut last_reboot_deferral_day: u64 = 0; // Day the reboot was last held for an alarm
+    let mut low_rssi_samples: u32 = 0; // Consecutive WiFi checks with a weak signal
+    let mut last_rssi_warning: Option<Instant> = None;
+    let mut demo_alarms = if run_demo {
+        log::info!("Demo schedule enabled, playing each alarm type once");
+        demo_schedule()
+    } else {
+        Vec::new()
+    }
+    .into_iter();
+    let mut last_demo_alarm: Option<Instant> = None;
 
     // Main loop
     loop {
+        // Restart the buzzer thread if it has died, backing off each time. A
+        // failed restart loses the pins, so from then on, or once the
+        // attempts run out, the clock keeps running without sound.
+        let restart_interval = BUZZER_RESTART_INTERVAL_SECS << buzzer_restarts.min(6);
+        let restart_due =
+            last_buzzer_restart.is_none_or(|t| t.elapsed().as_secs() >= restart_interval);
+        if let Some(thread) = buzzer_thread.take_if(|t| restart_due && t.is_finished()) {
+            // Panics abort the firmware, so a finished thread has returned
+            // normally and handed its pins back
+            let pins = thread
+                .join()
+                .map_err(|_| anyhow::anyhow!("buzzer thread panicked"));
+            if buzzer_restarts >= BUZZER_RESTART_MAX_ATTEMPTS {
+                log::error!(
+                    "Buzzer thread exited again after {} restarts, giving up, alarms will be silent",
+                    buzzer_restarts
+                );
+            } else {
+                buzzer_restarts += 1;
+                log::error!(
+                    "Buzzer thread exited unexpectedly, restarting it (attempt {} of {})",
+                    buzzer_restarts,
+                    BUZZER_RESTART_MAX_ATTEMPTS
+                );
+                let restarted =
+                    pins.and_then(|(pin, motor_pin)| spawn_buzzer_thread(pin, motor_pin));
+                match restarted {
+                    Ok((tx, thread)) => {
+                        buzzer_tx = tx;
+                        buzzer_thread = Some(thread);
+                    }
+                    Err(e) => log::error!(
+                        "Failed to restart buzzer thread, giving up, alarms will be silent: {:?}",
+                        e
+                    ),
+                }
+            }
+            last_buzzer_restart = Some(Instant::now());
+        }
+
         // Check WiFi status periodically
         if let Ok(elapsed) = last_wifi_check.elapsed() {
             if elapsed.as_secs() * 1000 > WIFI_CHECK_INTERVAL {
-                if !wifi_is_connected(&wifi) {
-                    log::warn!("WiFi connection lost. Attempting to reconnect...");
-                    if let Err(e) = wifi.connect() {
-                        log::error!("Failed to reconnect to WiFi: {:?}", e);
+                let associated = wifi_is_connected(&wifi);
+                if !associated || !wifi.is_up().unwrap_or(false) {
+                    SYSLOG_ONLINE.store(false, Ordering::Relaxed);
+                    // Associated without a lease only needs DHCP to be retried
+                    let connected = if associated {
+                        log::debug!("WiFi associated without an IP address, retrying DHCP");
+                        Ok(())
+                    } else {
+                        wifi_lost_log.log(
+                            log::Level::Warn,
+                            "WiFi connection lost. Attempting to reconnect...".to_string(),
+                        );
+                        if !wifi_down {
+                            emit_event(Event::WifiDisconnected);
+                            wifi_down = true;
+                        }
+                        wifi.connect()
+                    };
+                    if let Err(e) = connected {
+                        wifi_connect_log.log(
+                            log::Level::Error,
+                            format!("Failed to reconnect to WiFi: {:?}", e),
+                        );
                     } else if let Err(e) = wifi.wait_netif_up() {
-                        log::error!("Failed to get IP address: {:?}", e);
+                        wifi_dhcp_log.log(
+                            log::Level::Error,
+                            format!("Failed to get IP address: {:?}", e),
+                        );
                     } else {
+                        wifi_lost_log.flush();
+                        wifi_connect_log.flush();
+                        wifi_dhcp_log.flush();
+
                         let ip_info = wifi.wifi().sta_netif().get_ip_info()?;
                         log::info!("WiFi reconnected, IP: {}", ip_info.ip);
+                        SYSLOG_ONLINE.store(true, Ordering::Relaxed);
+                        daily_stats.wifi_reconnects += 1;
+                        emit_event(Event::WifiReconnected {
+                            ip: ip_info.ip.to_string(),
+                        });
+                        wifi_down = false;
+
+                        if let Some(mdns) = mdns.as_mut() {
+                            if let Err(e) = announce_mdns(mdns, &device_name) {
+                                log::warn!("Failed to re-announce mDNS hostname: {:?}", e);
+                            }
+                        }
+
+                        // Resync now rather than waiting for the next SNTP poll,
+                        // but don't hammer the server if the connection flaps
+                        let resync_due = last_ntp_resync
+                            .is_none_or(|t| t.elapsed().as_secs() >= NTP_RESYNC_MIN_INTERVAL_SECS);
+                        if resync_due {
+                            request_sntp_resync();
+                            last_ntp_resync = Some(Instant::now());
+                        } else {
+                            log::debug!("Skipping SNTP resync, last one was too recent");
+                        }
                     }
                 } else {
                     log::debug!("WiFi connection is stable");
+                    SYSLOG_ONLINE.store(true, Ordering::Relaxed);
+
+                    // Retry sooner than the regular poll once a sync is overdue
+                    let since_sync = last_sntp_sync().unwrap_or(boot_time).elapsed().as_secs();
+                    if since_sync >= NTP_SYNC_INTERVAL + NTP_SYNC_RETRY_INTERVAL_SECS {
+                        if !sntp_retrying {
+                            log::warn!(
+                                "No SNTP sync for {} s, retrying every {} s",
+                                since_sync,
+                                NTP_SYNC_RETRY_INTERVAL_SECS
+                            );
+                            sntp_retrying = true;
+                            record_sync(None, false);
+                            if let SyncChimeRepeat::AfterFailure = SYNC_CHIME_REPEAT {
+                                sync_chime_due = SYNC_CHIME_ENABLED;
+                            }
+                        }
+                        let retry_due = last_ntp_resync
+                            .is_none_or(|t| t.elapsed().as_secs() >= NTP_SYNC_RETRY_INTERVAL_SECS);
+                        if retry_due {
+                            request_sntp_resync();
+                            last_ntp_resync = Some(Instant::now());
+                        }
+                    } else if sntp_retrying {
+                        log::info!("SNTP syncing again, back to every {} s", NTP_SYNC_INTERVAL);
+                        sntp_retrying = false;
+                    }
+
+                    let rssi = wifi_rssi();
+                    if let Some(rssi) = rssi {
+                        daily_stats.record_rssi(rssi);
+                    }
+
+                    if RSSI_WARNING_ENABLED {
+                        match rssi {
+                            Some(rssi) if rssi < RSSI_WARNING_THRESHOLD_DBM => {
+                                low_rssi_samples += 1;
+                                log::debug!("Weak WiFi signal: {} dBm", rssi);
+                            }
+                            _ => low_rssi_samples = 0,
+                        }
+
+                        let warning_due = last_rssi_warning
+                            .is_none_or(|t| t.elapsed().as_secs() >= RSSI_WARNING_INTERVAL_SECS);
+                        if low_rssi_samples >= RSSI_LOW_SAMPLES && warning_due {
+                            log::warn!(
+                                "WiFi signal below {} dBm for {} checks",
+                                RSSI_WARNING_THRESHOLD_DBM,
+                                low_rssi_samples
+                            );
+                            send_to_buzzer(
+                                &buzzer_tx,
+                                BuzzerMessage::PlayAlarm {
+                                    repeat_count: RSSI_WARNING_REPEATS,
+                                    frequency: RSSI_WARNING_FREQUENCY,
+                                },
+                                "RSSI warning",
+                            );
+                            last_rssi_warning = Some(Instant::now());
+                        }
+                    }
                 }
                 last_wifi_check = SystemTime::now();
             }
         }
 
-        // Check if we've entered a new hour
-        if let Ok(current_time) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
-            let now = current_time.as_secs();
+        // Confirm a fresh SNTP sync audibly, when one is wanted
+        let synced_at = last_sntp_sync();
+        if synced_at != last_seen_sync {
+            last_seen_sync = synced_at;
+            if sync_chime_due {
+                if BUZZER_PENDING.load(Ordering::Relaxed) == 0 {
+                    send_to_buzzer(
+                        &buzzer_tx,
+                        BuzzerMessage::PlayMelody {
+                            notes: configured_melody(SYNC_CHIME_MELODY_NAME),
+                            repeat_count: 1,
+                            growth: None,
+                        },
+                        "sync chime",
+                    );
+                } else {
+                    log::debug!("Buzzer busy, skipping sync chime");
+                }
+                sync_chime_due = false;
+            }
+        }
+
+        // Keep the external RTC in step with SNTP
+        if let Some(rtc) = rtc.as_mut() {
+            let write_due =
+                last_rtc_write.is_none_or(|t| t.elapsed().as_secs() >= DS3231_WRITE_INTERVAL_SECS);
+            if write_due && sntp.get_sync_status() == SyncStatus::Completed {
+                let now = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                match ds3231_write_secs(rtc, now) {
+                    Ok(()) => log::debug!("DS3231 updated from SNTP time"),
+                    Err(e) => log::warn!("Failed to update DS3231: {:?}", e),
+                }
+                last_rtc_write = Some(Instant::now());
+            }
+        }
+
+        // Announce the IP address once per long press of the button, or the
+        // time when a shorter press is released
+        if let Some(button) = &announce_button {
+            if button.is_low() {
+                let pressed_at = *button_pressed_at.get_or_insert_with(Instant::now);
+                if IP_ANNOUNCE_ENABLED
+                    && !ip_announced
+                    && pressed_at.elapsed().as_millis() >= IP_ANNOUNCE_HOLD_MS
+                {
+                    if manual_trigger_allowed(&mut last_manual_trigger, "IP announcement") {
+                        announce_ip(&wifi, &buzzer_tx);
+                    }
+                    ip_announced = true;
+                }
+            } else {
+                if let Some(pressed_at) = button_pressed_at.take() {
+                    let short_press = !IP_ANNOUNCE_ENABLED
+                        || pressed_at.elapsed().as_millis() < IP_ANNOUNCE_HOLD_MS;
+                    if TIME_ANNOUNCE_ENABLED
+                        && short_press
+                        && manual_trigger_allowed(&mut last_manual_trigger, "time announcement")
+                    {
+                        announce_time(&buzzer_tx);
+                    }
+                }
+                ip_announced = false;
+            }
+        }
+
+        // Note when the PIR sensor last saw motion
+        if let Some(pir) = &pir {
+            if pir.is_high() {
+                if last_motion.is_none_or(|t| t.elapsed().as_secs() >= PIR_PRESENCE_WINDOW_SECS) {
+                    log::info!("Motion detected");
+                }
+                last_motion = Some(Instant::now());
+            }
+        }
+
+        // Follow the power source, logging each change
+        if let Some(power_sense) = &power_sense {
+            let source = if power_sense.is_high() {
+                PowerSource::Usb
+            } else {
+                PowerSource::Battery
+            };
+            if power_source != Some(source) {
+                log::info!("Running on {}", source.name());
+                power_source = Some(source);
+            }
+        }
+
+        // Check the battery voltage
+        if let Some(channel) = battery.as_mut() {
+            let check_due = last_battery_check
+                .is_none_or(|t| t.elapsed().as_secs() >= BATTERY_CHECK_INTERVAL_SECS);
+            if check_due {
+                match read_battery_mv(channel) {
+                    Ok(battery_mv) => {
+                        log::debug!("Battery voltage: {} mV", battery_mv);
+                        let warning_due = last_battery_warning
+                            .is_none_or(|t| t.elapsed().as_secs() >= BATTERY_WARNING_INTERVAL_SECS);
+                        if battery_mv < BATTERY_LOW_MV && warning_due {
+                            log::warn!(
+                                "Battery low: {} mV (threshold {} mV)",
+                                battery_mv,
+                                BATTERY_LOW_MV
+                            );
+                            send_to_buzzer(
+                                &buzzer_tx,
+                                BuzzerMessage::PlayAlarm {
+                                    repeat_count: BATTERY_WARNING_REPEATS,
+                                    frequency: BATTERY_WARNING_FREQUENCY,
+                                },
+                                "low battery warning",
+                            );
+                            last_battery_warning = Some(Instant::now());
+                        }
+                    }
+                    Err(e) => log::warn!("Failed to read battery voltage: {:?}", e),
+                }
+                last_battery_check = Some(Instant::now());
+            }
+        }
+
+        // Play the next demo alarm once the buzzer is idle and the spacing has passed
+        let demo_due = last_demo_alarm.is_none_or(|t| t.elapsed().as_secs() >= DEMO_SPACING_SECS);
+        if demo_due && BUZZER_PENDING.load(Ordering::Relaxed) == 0 {
+            if let Some(alarm) = demo_alarms.next() {
+                log::info!("Demo: {}", alarm.alarm_type.name());
+                alarm.send(&buzzer_tx);
+                last_demo_alarm = Some(Instant::now());
+            }
+        }
+
+        // Check if we've entered a new hour
+        if let Some(now) = alarm_clock_secs() {
+            // Don't evaluate alarms until the clock holds a plausible time
+            if now < MIN_VALID_EPOCH_SECS {
+                if !alarms_suppressed {
+                    log::warn!("System time not valid yet, alarms suppressed pending sync");
+                    alarms_suppressed = true;
+                }
+                thread::sleep(Duration::from_millis(500));
+                continue;
+            }
+            if alarms_suppressed {
+                log::info!("Valid system time established, resuming alarms");
+                alarms_suppressed = false;
+            }
+
+            // Handle the clock stepping backwards since the last pass
+            if let Some(previous) = last_clock_secs {
+                let step = handle_backward_jump(
+                    previous,
+                    now,
+                    BACKWARD_JUMP_THRESHOLD_SECS,
+                    BACKWARD_JUMP_POLICY,
+                    &mut last_fired,
+                );
+                if let Some(step) = step {
+                    log::warn!("Clock stepped back by {} s", step);
+                }
+            }
+            last_clock_secs = Some(now);
+
+            // Apply the local timezone offset
+            let now_with_tz = now + UTC_OFFSET_SECS;
+
+            if seed_last_fired {
+                let now_minute = now_with_tz / 60;
+                let due = SCHEDULE
+                    .due_times(now_minute)
+                    .into_iter()
+                    .map(|(alarm_type, due_minute)| (Trigger::Alarm(alarm_type), due_minute));
+                seed_fired(due, now_minute, &mut last_fired);
+                log::info!("Restarted on purpose, not repeating alarms already due");
+                seed_last_fired = false;
+            }
+
+            let mins = (now_with_tz / 60) % 60;
+            let hours = (now_with_tz / 3600) % 24;
+
+            // Log current time every 5 minutes but only once per interval
+            let current_log_key = (hours * 60 + mins) as i64; // Convert to i64 to match last_log_time
+            if current_log_key != last_log_time {
+                log::info!(
+                    "Current time: {:02}:{:02}, uptime {}",
+                    hours,
+                    mins,
+                    format_uptime(boot_time.elapsed())
+                );
+                last_log_time = current_log_key;
+
+                if DEBUG_ON {
+                    send_to_buzzer(&buzzer_tx, BuzzerMessage::TestBeep, "debug test beep");
+                }
+            }
+
+            // Note when today is a configured exception date
+            let day = now_with_tz / 86_400;
+            if is_skip_date(ALARM_SKIP_DATES, day) && day != last_skip_day {
+                let (year, month, mday) = civil_from_days(day);
+                log::info!("Alarms skipped today ({}-{:02}-{:02})", year, month, mday);
+                last_skip_day = day;
+            }
+
+            // Log the daily health summary once a day
+            if DAILY_SUMMARY_ENABLED
+                && hours == DAILY_SUMMARY_HOUR
+                && mins == DAILY_SUMMARY_MINUTE
+                && day != last_summary_day
+            {
+                daily_stats.log_and_reset(boot_time.elapsed());
+                last_summary_day = day;
+            }
+
+            let conditions = Conditions {
+                present: PIR_ENABLED.then(|| {
+                    last_motion.is_some_and(|t| t.elapsed().as_secs() < PIR_PRESENCE_WINDOW_SECS)
+                }),
+                power_source,
+            };
+
+            // Scheduled daily reboot, held off while an alarm is sounding
+            if DAILY_REBOOT_ENABLED {
+                let minute_of_day = hours * 60 + mins;
+                let reboot_at = DAILY_REBOOT_HOUR * 60 + DAILY_REBOOT_MINUTE;
+                // Modulo a day, so a notice for a reboot just after midnight
+                // comes the evening before
+                let notice_at = (reboot_at + 1440 - DAILY_REBOOT_NOTICE_MINS % 1440) % 1440;
+                if minute_of_day == notice_at && day != last_reboot_notice_day {
+                    log::info!(
+                        "Scheduled reboot at {:02}:{:02}",
+                        DAILY_REBOOT_HOUR,
+                        DAILY_REBOOT_MINUTE
+                    );
+                    last_reboot_notice_day = day;
+                }
+
+                // Minutes since the reboot time, so the window can run past midnight
+                let since_reboot_at = (minute_of_day + 1440 - reboot_at) % 1440;
+                let in_window = since_reboot_at < DAILY_REBOOT_WINDOW_MINS;
+                if in_window && boot_time.elapsed().as_secs() >= DAILY_REBOOT_MIN_UPTIME_SECS {
+                    let upcoming = alarm_due_within(
+                        now_with_tz,
+                        DAILY_REBOOT_ALARM_GUARD_MINS,
+                        &last_fired,
+                        conditions,
+                    );
+                    if let Some(alarm_type) = upcoming {
+                        if day != last_reboot_deferral_day {
+                            log::info!(
+                                "Deferring scheduled reboot until the {} has sounded",
+                                alarm_type.name()
+                            );
+                            last_reboot_deferral_day = day;
+                        }
+                    } else if BUZZER_PENDING.load(Ordering::Relaxed) == 0 {
+                        // The idle buzzer thread always leaves the pin low
+                        log::info!("Rebooting for scheduled maintenance");
+                        thread::sleep(Duration::from_millis(100)); // Let the log drain
+                        unsafe { esp_idf_svc::sys::esp_restart() };
+                    } else {
+                        log::debug!("Buzzer busy, deferring scheduled reboot");
+                    }
+                }
+            }
+
+            // Soft pre-warning beeps ahead of the upcoming alarms
+            send_prewarning(
+                &buzzer_tx,
+                AlarmType::HourChime,
+                &HOUR_CHIME_PREWARNING,
+                HOUR_CHIME_MINUTE,
+                now_with_tz,
+                &mut last_fired,
+                conditions,
+            );
+            send_prewarning(
+                &buzzer_tx,
+                AlarmType::Reminder,
+                &REMINDER_PREWARNING,
+                REMINDER_MINUTE,
+                now_with_tz,
+                &mut last_fired,
+                conditions,
+            );
+
+            // Alarms due this minute, sounded after resolving collisions
+            let tick = scheduler::tick(&ALARMS, now_with_tz, conditions, &mut last_fired);
+
+            for (alarm_type, reason) in tick.suppressed {
+                log::info!("{} suppressed: {}", alarm_type.name(), reason.name());
+                emit_event(Event::AlarmSuppressed {
+                    alarm: alarm_type.name(),
+                    reason: reason.name(),
+                });
+                daily_stats.alarms_suppressed += 1;
+            }
+            for alarm in &tick.silent {
+                log::info!(
+                    "Hour chime for {}:{:02} is configured silent",
+                    alarm.due_minute / 60 % 24,
+                    alarm.due_minute % 60
+                );
+            }
+            for alarm in &tick.skipped {
+                log::info!(
+                    "Skipping {} due at the same time as {}",
+                    alarm.alarm_type.name(),
+                    tick.fired[0].alarm_type.name()
+                );
+            }
+            for due in tick.fired {
+                let alarm = fired_alarm(due);
+                daily_stats.alarms_fired += 1;
+                emit_event(Event::AlarmFired {
+                    alarm: alarm.alarm_type.name(),
+                });
+                alarm.send(&buzzer_tx);
+            }
+        }
+
+        thread::sleep(Duration::from_millis(500));
+    }
+}
+
+// Current time in seconds since the epoch, as seen by alarm evaluation
+fn alarm_clock_secs() -> Option<u64> {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+
+    #[cfg(feature = "simulated-clock")]
+    let now = simulated_clock_secs(now);
+
+    Some(now)
+}
+
+// Scale time elapsed since the first valid reading by SIMULATED_CLOCK_SPEED
+#[cfg(feature = "simulated-clock")]
+fn simulated_clock_secs(now: u64) -> u64 {
+    static START: OnceLock<(Instant, u64)> = OnceLock::new();
+
+    // Don't anchor the simulation to a not-yet-synced clock
+    if now < MIN_VALID_EPOCH_SECS {
+        return now;
+    }
+
+    let (at, wall) = START.get_or_init(|| (Instant::now(), now));
+    wall + at.elapsed().as_secs() * SIMULATED_CLOCK_SPEED
+}
+
+// Who needs to be around for an alarm of the given type to sound
+fn alarm_presence(alarm_type: AlarmType) -> Presence {
+    match alarm_type {
+        AlarmType::HourChime => HOUR_CHIME_PRESENCE,
+        AlarmType::Reminder => REMINDER_PRESENCE,
+        AlarmType::Bedtime => BEDTIME_PRESENCE,
+        AlarmType::QuarterChime => QUARTER_CHIME_PRESENCE,
+    }
+}
+
+// The first alarm that will sound within `window_mins` of the given local
+// time, counting the current minute, if any
+fn alarm_due_within(
+    now_with_tz: u64,
+    window_mins: u64,
+    last_fired: &HashMap<Trigger, u64>,
+    conditions: Conditions,
+) -> Option<AlarmType> {
+    let now_minute = now_with_tz / 60;
+
+    for due_minute in now_minute..=now_minute + window_mins {
+        for (alarm_type, due) in SCHEDULE.due_times(due_minute) {
+            if due == due_minute
+                && !already_fired(Trigger::Alarm(alarm_type), due_minute, last_fired)
+                && evaluate_policy(&POLICY, due_minute * 60, alarm_type, conditions).is_ok()
+            {
+                return Some(alarm_type);
+            }
+        }
+    }
+
+    None
+}
+
+// The melody configured for an alarm, falling back to a safe default so a
+// dangling name never leaves the alarm silent
+fn configured_melody(name: &str) -> &'static [Note] {
+    resolve_melody(MELODIES, name, FALLBACK_MELODY).unwrap_or_else(|fallback| {
+        log::warn!("Unknown melody '{}', playing the fallback melody", name);
+        fallback
+    })
+}
+
+// The melody and output for an alarm the scheduler says is due
+fn fired_alarm(due: DueAlarm) -> FiredAlarm {
+    let (hours, mins) = (due.due_minute / 60 % 24, due.due_minute % 60);
+    let (output, notes) = match due.alarm_type {
+        AlarmType::QuarterChime => {
+            log::info!("Westminster chime at {}:{:02}", hours, mins);
+            (HOUR_CHIME_OUTPUT, westminster_melody(mins))
+        }
+        AlarmType::HourChime => {
+            log::info!("ALARM! It's now {}:{:02}", hours, mins);
+            let notes = if WESTMINSTER_CHIME_ENABLED {
+                configured_melody(HOUR_STRIKE_MELODY_NAME)
+            } else {
+                configured_melody(HOUR_CHIME_MELODY_NAME)
+            };
+            (HOUR_CHIME_OUTPUT, notes)
+        }
+        AlarmType::Reminder => {
+            log::info!("ALARM! It's now {}:{:02}", hours, mins);
+            (REMINDER_OUTPUT, configured_melody(REMINDER_MELODY_NAME))
+        }
+        AlarmType::Bedtime => {
+            log::info!("Bedtime chime at {}:{:02}", hours, mins);
+            (AlarmOutput::Buzzer, configured_melody(BEDTIME_MELODY_NAME))
+        }
+    };
+
+    FiredAlarm {
+        alarm_type: due.alarm_type,
+        output,
+        notes,
+        repeat_count: due.repeat_count,
+    }
+}
+
+// The Westminster changes for the quarter starting at `minute` past the hour
+fn westminster_melody(minute: u64) -> &'static [Note] {
+    configured_melody(WESTMINSTER_MELODY_NAMES[(minute / 15 % 4) as usize])
+}
+
+// One of each alarm type as configured, for the demo schedule
+fn demo_schedule() -> Vec<FiredAlarm> {
+    let mut alarms = vec![
+        FiredAlarm {
+            alarm_type: AlarmType::HourChime,
+            output: HOUR_CHIME_OUTPUT,
+            notes: configured_melody(HOUR_CHIME_MELODY_NAME),
+            repeat_count: 1,
+        },
+        FiredAlarm {
+            alarm_type: AlarmType::Reminder,
+            output: REMINDER_OUTPUT,
+            notes: configured_melody(REMINDER_MELODY_NAME),
+            repeat_count: REMINDER_REPEATS,
+        },
+    ];
+    if BEDTIME_CHIME_ENABLED {
+        alarms.push(FiredAlarm {
+            alarm_type: AlarmType::Bedtime,
+            output: AlarmOutput::Buzzer,
+            notes: configured_melody(BEDTIME_MELODY_NAME),
+            repeat_count: 1,
+        });
+    }
+    if WESTMINSTER_CHIME_ENABLED {
+        alarms.push(FiredAlarm {
+            alarm_type: AlarmType::QuarterChime,
+            output: HOUR_CHIME_OUTPUT,
+            notes: westminster_melody(15),
+            repeat_count: 1,
+        });
+    }
+    alarms
+}
+
+// Send the pre-warning beep for the alarm at `minute` past the hour once it's due
+fn send_prewarning(
+    buzzer_tx: &BuzzerChannel,
+    alarm_type: AlarmType,
+    prewarning: &Option<PreWarning>,
+    minute: u64,
+    now_with_tz: u64,
+    last_fired: &mut HashMap<Trigger, u64>,
+    conditions: Conditions,
+) {
+    let Some(prewarning) = prewarning else {
+        return;
+    };
+
+    // Work out when the alarm fires next and in which hour
+    let secs_until = (minute * 60 + 3600 - now_with_tz % 3600) % 3600;
+    let alarm_time = now_with_tz + secs_until;
+    let alarm_hour = (alarm_time / 3600) % 24;
+
+    if secs_until == 0 || secs_until > prewarning.lead_secs {
+        return;
+    }
+    // Keyed by the alarm's due time, as the pre-warning fires ahead of it
+    let trigger = Trigger::PreWarning(alarm_type);
+    let alarm_minute = alarm_time / 60;
+    if already_fired(trigger, alarm_minute, last_fired) {
+        return;
+    }
+    last_fired.insert(trigger, alarm_minute);
+
+    // Stay quiet whenever the alarm itself would be suppressed
+    if evaluate_policy(&POLICY, alarm_time, alarm_type, conditions).is_err() {
+        return;
+    }
+
+    log::info!(
+        "Pre-warning: alarm at {}:{:02} in {} s",
+        alarm_hour,
+        minute,
+        secs_until
+    );
+    send_to_buzzer(
+        buzzer_tx,
+        BuzzerMessage::PlayTone {
+            frequency: prewarning.frequency,
+            duration_ms: prewarning.duration_ms,
+        },
+        "pre-warning",
+    );
+}
+
+// Write an event to the serial console as a single JSON line.
+// Values are fixed names, numbers and IP addresses, so need no escaping.
+fn emit_event(event: Event) {
+    if SERIAL_OUTPUT == SerialOutput::Logs {
+        return;
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let (kind, details) = match event {
+        Event::AlarmFired { alarm } => ("alarm_fired", format!(r#","alarm":"{}""#, alarm)),
+        Event::AlarmSuppressed { alarm, reason } => (
+            "alarm_suppressed",
+            format!(r#","alarm":"{}","reason":"{}""#, alarm, reason),
+        ),
+        Event::WifiDisconnected => ("wifi_disconnected", String::new()),
+        Event::WifiReconnected { ip } => ("wifi_reconnected", format!(r#","ip":"{}""#, ip)),
+        Event::TimeSynced { offset_ms } => (
+            "time_synced",
+            match offset_ms {
+                Some(offset_ms) => format!(r#","offset_ms":{}"#, offset_ms),
+                None => String::new(),
+            },
+        ),
+    };
+
+    println!(
+        r#"{{"event":"{}","timestamp":{}{}}}"#,
+        kind, timestamp, details
+    );
+}
+
+// Start the thread that forwards queued log lines to the syslog server
+fn start_syslog(host: &'static str, hostname: &str) -> Result<()> {
+    let socket = UdpSocket::bind("0.0.0.0:0").context("Failed to open syslog socket")?;
+    let (tx, rx) = mpsc::sync_channel(SYSLOG_QUEUE_LEN);
+    let hostname = hostname.to_string();
+
+    thread::Builder::new()
+        .name("syslog".to_string())
+        .stack_size(SYSLOG_THREAD_STACK_SIZE)
+        .spawn(move || syslog_task(rx, socket, host, &hostname))
+        .context("Failed to spawn syslog thread")?;
+
+    // Only the first start can succeed, and this is the only caller
+    let _ = LOGGER.syslog.set(tx);
+    log::info!("Forwarding logs to syslog at {}:{}", host, SYSLOG_PORT);
+    Ok(())
+}
+
+// Send queued log lines while the network is up, leaving them queued while
+// it is down. A line that fails to send is dropped rather than retried.
+fn syslog_task(rx: Receiver<(log::Level, String)>, socket: UdpSocket, host: &str, hostname: &str) {
+    let mut server: Option<SocketAddr> = None;
+    let mut resolve_failed = false; // Whether the failure has been logged
+
+    loop {
+        if !SYSLOG_ONLINE.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_millis(500));
+            continue;
+        }
+        let Ok((level, message)) = rx.recv() else {
+            break;
+        };
+
+        if server.is_none() {
+            server = (host, SYSLOG_PORT)
+                .to_socket_addrs()
+                .ok()
+                .and_then(|mut addrs| addrs.next());
+            match server {
+                Some(_) => resolve_failed = false,
+                None if !resolve_failed => {
+                    log::warn!("Failed to resolve syslog server '{}'", host);
+                    resolve_failed = true;
+                }
+                None => {}
+            }
+        }
+        let Some(server) = server else {
+            continue;
+        };
+
+        let dropped = SYSLOG_DROPPED.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            let notice = format!(
+                "{} log lines dropped while the syslog queue was full",
+                dropped
+            );
+            let _ = socket.send_to(
+                syslog_line(log::Level::Warn, hostname, &notice).as_bytes(),
+                server,
+            );
+        }
+        let _ = socket.send_to(syslog_line(level, hostname, &message).as_bytes(), server);
+    }
+}
+
+// Format a log line as an RFC 5424 syslog message, with no timestamp until
+// the clock holds a plausible time
+fn syslog_line(level: log::Level, hostname: &str, message: &str) -> String {
+    let severity = match level {
+        log::Level::Error => 3,
+        log::Level::Warn => 4,
+        log::Level::Info => 6,
+        log::Level::Debug | log::Level::Trace => 7,
+    };
+    let priority = u32::from(SYSLOG_FACILITY) * 8 + severity;
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let timestamp = if now >= MIN_VALID_EPOCH_SECS {
+        let (year, month, day) = civil_from_days(now / 86_400);
+        let time_of_day = now % 86_400;
+        format!(
+            "{}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            year,
+            month,
+            day,
+            time_of_day / 3600,
+            time_of_day / 60 % 60,
+            time_of_day % 60
+        )
+    } else {
+        "-".to_string()
+    };
+
+    format!(
+        "<{}>1 {} {} esp32-alarm - - - {}",
+        priority, timestamp, hostname, message
+    )
+}
+
+// Send a message to the buzzer thread, or just log it in silent mode
+// Once the channel is found closed, further messages are dropped with a
+// debug log until the buzzer thread is restarted with a fresh channel.
+fn send_to_buzzer(buzzer_tx: &BuzzerChannel, message: BuzzerMessage, what: &str) {
+    if SILENT_MODE {
+        log::info!("Silent mode: {} would have sounded", what);
+        return;
+    }
+
+    if buzzer_tx.broken.get() {
+        log::debug!("Buzzer channel closed, dropping {}", what);
+        return;
+    }
+
+    BUZZER_PENDING.fetch_add(1, Ordering::Relaxed);
+    if let Err(e) = buzzer_tx.tx.send(message) {
+        BUZZER_PENDING.fetch_sub(1, Ordering::Relaxed);
+        log::error!(
+            "Failed to send {} to buzzer thread: {:?}, dropping alarms until it restarts",
+            what,
+            e
+        );
+        buzzer_tx.broken.set(true);
+    }
+}
+
+// Warn when an output is wired to a pin the chip samples at reset
+fn warn_if_strapping_pin(what: &str, gpio: i32) {
+    if STRAPPING_PINS.contains(&gpio) {
+        log::warn!(
+            "{} is on strapping pin GPIO{}; if the board fails to boot with it wired up, move it to another pin",
+            what,
+            gpio
+        );
+    }
+}
+
+// Beep a distinctive pattern when boot fails before the buzzer thread is up
+fn signal_boot_failure<T: OutputPin>(pin: impl Peripheral<P = T>) {
+    let Ok(mut buzzer) = PinDriver::output(pin) else {
+        return;
+    };
+
+    for _ in 0..BOOT_FAILURE_BEEPS {
+        if play_tone(&mut buzzer, BOOT_FAILURE_FREQUENCY, BOOT_FAILURE_BEEP_MS).is_err() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(BOOT_FAILURE_BEEP_MS));
+    }
+}
+
+// Check whether the self-test jumper pulls the diagnostics pin low
+fn self_test_requested<T: InputPin + OutputPin>(pin: impl Peripheral<P = T>) -> Result<bool> {
+    let mut pin = PinDriver::input(pin)?;
+    pin.set_pull(Pull::Up)?;
+
+    // Give the pull-up a moment to settle before sampling
+    thread::sleep(Duration::from_millis(10));
+    Ok(pin.is_low())
+}
+
+// Log the firmware, ESP-IDF and chip versions for bug reports
+fn log_version_info() {
+    let idf_version = unsafe { std::ffi::CStr::from_ptr(esp_idf_svc::sys::esp_get_idf_version()) };
+    let mut chip_info = esp_idf_svc::sys::esp_chip_info_t::default();
+    unsafe { esp_idf_svc::sys::esp_chip_info(&mut chip_info) };
+
+    log::info!(
+        "Firmware {} ({}), ESP-IDF {}, chip model {} revision {}",
+        FIRMWARE_VERSION,
+        GIT_HASH,
+        idf_version.to_string_lossy(),
+        chip_info.model,
+        chip_info.revision
+    );
+}
+
+// Beep through a few frequencies with the LED lit and log basic diagnostics
+fn run_self_test<B: OutputPin, L: OutputPin>(
+    buzzer_pin: impl Peripheral<P = B>,
+    led_pin: impl Peripheral<P = L>,
+) -> Result<()> {
+    log::info!("Self-test jumper detected, running hardware self-test");
+
+    let mut buzzer =
+        PinDriver::output(buzzer_pin).context("Failed to initialize buzzer pin for self-test")?;
+    let mut led = PinDriver::output(led_pin).context("Failed to initialize self-test LED pin")?;
+
+    let mut chip_info = esp_idf_svc::sys::esp_chip_info_t::default();
+    unsafe { esp_idf_svc::sys::esp_chip_info(&mut chip_info) };
+    log::info!(
+        "Self-test: chip model {}, revision {}, {} cores",
+        chip_info.model,
+        chip_info.revision,
+        chip_info.cores
+    );
+    log::info!("Self-test: free heap {} bytes", unsafe {
+        esp_idf_svc::sys::esp_get_free_heap_size()
+    });
 
-            // Apply UTC+8 timezone adjustment
-            let now_with_tz = now + (8 * 3600); // Add 8 hours in seconds
+    for freq in SELF_TEST_FREQUENCIES {
+        log::info!("Self-test: {} Hz tone", freq);
+        led.set_high()?;
+        play_tone(&mut buzzer, freq, SELF_TEST_TONE_MS)?;
+        led.set_low()?;
+        thread::sleep(Duration::from_millis(BEEP_PAUSE_MS));
+    }
 
-            let mins = (now_with_tz / 60) % 60;
-            let hours = (now_with_tz / 3600) % 24;
+    log::info!("Self-test complete, continuing boot");
+    Ok(())
+}
 
-            // Log current time every 5 minutes but only once per interval
-            let current_log_key = (hours * 60 + mins) as i64; // Convert to i64 to match last_log_time
-            if current_log_key != last_log_time {
-                log::info!("Current time: {:02}:{:02}", hours, mins);
-                last_log_time = current_log_key;
+// Output pins. To move the buzzer or motor off a strapping pin, change
+// these and the matching peripherals.pins.gpioN uses in main().
+type BuzzerPin = Gpio5;
+type MotorPin = Gpio18;
 
-                if DEBUG_ON {
-                    if let Err(e) = buzzer_tx.send(BuzzerMessage::PlayAlarm {
-                        repeat_count: 3,
-                        frequency: 2800,
-                    }) {
-                        log::error!("Failed to send alarm to buzzer thread: {:?}", e);
-                    }
-                }
-            }
+// Buzzer thread handle, returning the buzzer and motor pins on exit
+type BuzzerThread = JoinHandle<(BuzzerPin, MotorPin)>;
 
-            // Only send alarms between 7:00 and 23:00
-            let is_alarm_time = hours >= 7 && hours <= 23;
+// Sending side of the buzzer channel, remembering once the receiver has gone
+struct BuzzerChannel {
+    tx: Sender<BuzzerMessage>,
+    broken: Cell<bool>,
+}
 
-            // Sound alarm at the start of each hour
-            if hours as i32 != last_hour && mins >= 0 && is_alarm_time {
-                last_hour = hours as i32;
-                log::info!("ALARM! It's now {}:00", hours);
+type BatteryChannel = AdcChannelDriver<'static, Gpio34, AdcDriver<'static, ADC1>>;
 
-                // Send alarm message to buzzer thread
-                // Set repeat count to the current hour and frequency to 2000Hz
-                if let Err(e) = buzzer_tx.send(BuzzerMessage::PlayAlarm {
-                    repeat_count: hours as u8,
-                    frequency: 2300,
-                }) {
-                    log::error!("Failed to send alarm to buzzer thread: {:?}", e);
-                }
-            }
+// Spawn the buzzer control thread, which hands its pins back when it exits
+fn spawn_buzzer_thread(
+    pin: BuzzerPin,
+    motor_pin: MotorPin,
+) -> Result<(BuzzerChannel, BuzzerThread)> {
+    let (buzzer_tx, buzzer_rx) = mpsc::channel();
+    BUZZER_PENDING.store(0, Ordering::Relaxed); // Nothing is queued on a new channel
 
-            // Sound alarm at 10 minutes past each hour
-            if hours as i32 != last_10_min_alarm && mins >= 10 && is_alarm_time {
-                last_10_min_alarm = hours as i32;
-                log::info!("ALARM! It's now {}:10", hours);
+    // Applies to threads spawned from this one until reset below
+    ThreadSpawnConfiguration {
+        name: Some(b"buzzer\0"),
+        stack_size: BUZZER_THREAD_STACK_SIZE,
+        priority: BUZZER_THREAD_PRIORITY,
+        ..Default::default()
+    }
+    .set()?;
 
-                // Send alarm message to buzzer thread with repeat count 3 and frequency 2600Hz
-                if let Err(e) = buzzer_tx.send(BuzzerMessage::PlayAlarm {
-                    repeat_count: 3,
-                    frequency: 2800,
-                }) {
-                    log::error!("Failed to send 10-min alarm to buzzer thread: {:?}", e);
+    let handle = thread::Builder::new()
+        .stack_size(BUZZER_THREAD_STACK_SIZE)
+        .spawn(move || {
+            let (mut pin, mut motor_pin) = (pin, motor_pin);
+            match (
+                PinDriver::output(&mut pin),
+                PinDriver::output(&mut motor_pin),
+            ) {
+                (Ok(mut buzzer), Ok(mut motor)) => {
+                    buzzer_control_task(buzzer_rx, &mut buzzer, &mut motor)
                 }
+                (Err(e), _) => log::error!("Failed to initialize buzzer pin: {:?}", e),
+                (_, Err(e)) => log::error!("Failed to initialize vibration motor pin: {:?}", e),
             }
-        }
+            (pin, motor_pin)
+        });
 
-        thread::sleep(Duration::from_millis(500));
-    }
+    ThreadSpawnConfiguration::default().set()?;
+
+    let channel = BuzzerChannel {
+        tx: buzzer_tx,
+        broken: Cell::new(false),
+    };
+    Ok((channel, handle?))
 }
 
 // Buzzer control task running in separate thread
-fn buzzer_control_task<T: OutputPin>(
+fn buzzer_control_task<T: OutputPin, M: OutputPin>(
     receiver: Receiver<BuzzerMessage>,
     buzzer: &mut PinDriver<'_, T, Output>,
+    motor: &mut PinDriver<'_, M, Output>,
 ) {
     log::info!("Buzzer control thread started");
 
@@ -188,12 +2297,56 @@ fn buzzer_control_task<T: OutputPin>(
                     log::error!("Error playing alarm: {:?}", e);
                 }
             }
+            Ok(BuzzerMessage::PlayMorse { text }) => {
+                log::debug!("Playing Morse message '{}'", text);
+                if let Err(e) = play_morse(buzzer, &text, MORSE_FREQUENCY) {
+                    log::error!("Error playing Morse message: {:?}", e);
+                }
+            }
+            Ok(BuzzerMessage::PlayMelody {
+                notes,
+                repeat_count,
+                growth,
+            }) => {
+                log::debug!(
+                    "Playing {}-note melody with {} repeats",
+                    notes.len(),
+                    repeat_count
+                );
+                if let Err(e) = play_melody(buzzer, notes, repeat_count, growth) {
+                    log::error!("Error playing melody: {:?}", e);
+                }
+            }
+            Ok(BuzzerMessage::TestBeep) => {
+                log::debug!("Playing test beep");
+                if let Err(e) =
+                    play_melody(buzzer, configured_melody(TEST_BEEP_MELODY_NAME), 1, None)
+                {
+                    log::error!("Error playing test beep: {:?}", e);
+                }
+            }
+            Ok(BuzzerMessage::Vibrate { pulses }) => {
+                log::debug!("Vibrating {} pulses", pulses);
+                if let Err(e) = play_vibration(motor, pulses) {
+                    log::error!("Error driving vibration motor: {:?}", e);
+                }
+            }
+            Ok(BuzzerMessage::PlayTone {
+                frequency,
+                duration_ms,
+            }) => {
+                log::debug!("Playing {} ms tone at {} Hz", duration_ms, frequency);
+                if let Err(e) = play_tone(buzzer, frequency, duration_ms) {
+                    log::error!("Error playing tone: {:?}", e);
+                }
+            }
             Err(e) => {
                 log::error!("Error receiving message in buzzer thread: {:?}", e);
                 // If channel is closed (e.g., main thread died), exit the thread
                 break;
             }
         }
+        BUZZER_PENDING.fetch_sub(1, Ordering::Relaxed);
     }
 
     log::info!("Buzzer control thread exiting");
@@ -205,8 +2358,14 @@ fn play_alarm_pattern<T: OutputPin>(
     repeat_count: u8,
     frequency: u32,
 ) -> Result<()> {
-    for _ in 0..repeat_count {
-        for _ in 0..BEEP_COUNT {
+    let start = Instant::now();
+
+    for repeat in 0..repeat_count {
+        if alarm_timed_out(start) {
+            break;
+        }
+
+        for _ in 0..grown_count(BEEP_COUNT, repeat, REPEAT_GROWTH) {
             play_tone(buzzer, frequency, BEEP_DURATION_MS)?;
             thread::sleep(Duration::from_millis(BEEP_PAUSE_MS));
         }
@@ -216,28 +2375,154 @@ fn play_alarm_pattern<T: OutputPin>(
     Ok(())
 }
 
+// Play a melody the given number of times, playing it more times in each
+// later repeat when growth is set
+fn play_melody<T: OutputPin>(
+    buzzer: &mut PinDriver<'_, T, Output>,
+    notes: &[Note],
+    repeat_count: u8,
+    growth: Option<RepeatGrowth>,
+) -> Result<()> {
+    let start = Instant::now();
+
+    for repeat in 0..repeat_count {
+        for _ in 0..grown_count(1, repeat, growth) {
+            if alarm_timed_out(start) {
+                return Ok(());
+            }
+
+            for note in notes {
+                play_tone(buzzer, note.frequency, note.duration_ms)?;
+                thread::sleep(Duration::from_millis(NOTE_PAUSE_MS));
+            }
+        }
+        thread::sleep(Duration::from_millis(PATTERN_PAUSE_MS));
+    }
+
+    Ok(())
+}
+
+// Pulse the vibration motor the given number of times
+fn play_vibration<M: OutputPin>(motor: &mut PinDriver<'_, M, Output>, pulses: u8) -> Result<()> {
+    let start = Instant::now();
+
+    for _ in 0..pulses {
+        if alarm_timed_out(start) {
+            break;
+        }
+
+        motor.set_high()?;
+        thread::sleep(Duration::from_millis(VIBRATION_PULSE_MS));
+        motor.set_low()?;
+        thread::sleep(Duration::from_millis(VIBRATION_PAUSE_MS));
+    }
+
+    Ok(())
+}
+
+// Check whether an alarm started at `start` has exceeded its maximum duration
+fn alarm_timed_out(start: Instant) -> bool {
+    if start.elapsed().as_secs() < MAX_ALARM_DURATION_SECS {
+        return false;
+    }
+
+    log::warn!(
+        "Alarm gave up after {} s without finishing its pattern",
+        MAX_ALARM_DURATION_SECS
+    );
+    true
+}
+
+// Play a text message as Morse code
+fn play_morse<T: OutputPin>(
+    buzzer: &mut PinDriver<'_, T, Output>,
+    text: &str,
+    frequency: u32,
+) -> Result<()> {
+    // Standard timing: dot = 1 unit, dash = 3, gaps of 1 / 3 / 7 units
+    // between elements, characters and words
+    let unit_ms = morse_unit_ms(MORSE_WPM);
+    let start = Instant::now();
+
+    let message = truncate_message(text, MORSE_MAX_CHARS);
+    if message.len() < text.len() {
+        log::warn!(
+            "Morse message longer than {} characters, playing '{}'",
+            MORSE_MAX_CHARS,
+            message
+        );
+    }
+
+    for word in message.split_whitespace() {
+        for c in word.chars() {
+            if alarm_timed_out(start) {
+                return Ok(());
+            }
+
+            let Some(code) = morse_code(c) else {
+                log::warn!("No Morse code for character '{}', skipping", c);
+                continue;
+            };
+
+            for symbol in code.chars() {
+                let units = if symbol == '-' { 3 } else { 1 };
+                play_tone(buzzer, frequency, units * unit_ms)?;
+                thread::sleep(Duration::from_millis(unit_ms));
+            }
+
+            // Complete the gap between characters
+            thread::sleep(Duration::from_millis(2 * unit_ms));
+        }
+
+        // Complete the gap between words
+        thread::sleep(Duration::from_millis(4 * unit_ms));
+    }
+
+    Ok(())
+}
+
 // Play a tone with the specified frequency and duration
 fn play_tone<T: OutputPin>(
     buzzer: &mut PinDriver<'_, T, Output>,
     freq_hz: u32,
     duration_ms: u64,
 ) -> Result<()> {
-    if freq_hz == 0 {
-        // If frequency is 0, just turn on for the duration
+    let clamped_ms = duration_ms.clamp(MIN_TONE_DURATION_MS, MAX_TONE_DURATION_MS);
+    if clamped_ms != duration_ms {
+        log::warn!(
+            "Tone duration {} ms out of range, clamped to {} ms",
+            duration_ms,
+            clamped_ms
+        );
+    }
+    let duration_ms = clamped_ms;
+    let freq_hz = hearing_frequency(freq_hz);
+
+    let half_period_us = match BUZZER_TYPE {
+        BuzzerType::Passive => half_period_us(freq_hz),
+        BuzzerType::Active => None,
+    };
+    let Some(half_period_us) = half_period_us else {
+        // Active buzzer or 0 Hz, just turn on for the duration
         buzzer.set_high()?;
         thread::sleep(Duration::from_millis(duration_ms));
         buzzer.set_low()?;
         return Ok(());
-    }
+    };
 
-    // Calculate half-period in microseconds
-    let half_period_us: u64 = 500_000 / freq_hz as u64;
     let start = SystemTime::now();
-    let duration_us = duration_ms * 1000;
-
-    // Threshold below which we'll use a spin loop instead of sleep
-    // FreeRTOS tick rate typically doesn't allow sleeps below 1ms (1000us)
-    const MIN_SLEEP_THRESHOLD_US: u64 = 1000;
+    let ramp_us = if TONE_RAMP_ENABLED {
+        TONE_RAMP_MS * 1000
+    } else {
+        0
+    };
+    let duration_us = duration_ms * 1000 + 2 * ramp_us;
+    log::debug!(
+        "Tone {} Hz for {} ms, about {} cycles",
+        freq_hz,
+        duration_ms,
+        cycles_for_duration(half_period_us, duration_us)
+    );
 
     let elapsed_us = || {
         SystemTime::now()
@@ -247,35 +2532,146 @@ fn play_tone<T: OutputPin>(
     };
 
     // Generate waveform for the specified duration
-    while elapsed_us() < duration_us {
-        buzzer.set_high()?;
-
-        if half_period_us >= MIN_SLEEP_THRESHOLD_US {
-            // For longer periods, sleep is efficient enough
-            thread::sleep(Duration::from_micros(half_period_us));
-        } else {
-            // For shorter periods, use a spin loop for better precision
-            let target = elapsed_us() + half_period_us;
-            while elapsed_us() < target {
-                // Busy wait (spin)
-            }
+    let mut last_yield_us = 0;
+    loop {
+        let now_us = elapsed_us();
+        if now_us >= duration_us {
+            break;
         }
+        let high_us = ramped_high_us(half_period_us, now_us, duration_us, ramp_us);
+
+        buzzer.set_high()?;
+        wait_half_period(high_us, &elapsed_us);
 
         buzzer.set_low()?;
+        wait_half_period(2 * half_period_us - high_us, &elapsed_us);
 
-        if half_period_us >= MIN_SLEEP_THRESHOLD_US {
-            thread::sleep(Duration::from_micros(half_period_us));
-        } else {
-            let target = elapsed_us() + half_period_us;
-            while elapsed_us() < target {
-                // Busy wait (spin)
+        // Yield at the end of a cycle, so the pause only stretches a low
+        let now_us = elapsed_us();
+        let spinning = should_spin(half_period_us, MIN_SLEEP_THRESHOLD_US);
+        if spinning && now_us - last_yield_us >= SPIN_YIELD_INTERVAL_MS * 1000 {
+            match SPIN_YIELD {
+                SpinYield::Never => {}
+                SpinYield::Yield => thread::yield_now(),
+                SpinYield::Tick => thread::sleep(Duration::from_micros(MIN_SLEEP_THRESHOLD_US)),
             }
+            last_yield_us = now_us;
         }
     }
 
     Ok(())
 }
 
+// A tone frequency moved into the configured hearing band, leaving the
+// steady 0 Hz tone alone
+fn hearing_frequency(freq_hz: u32) -> u32 {
+    if freq_hz == 0 {
+        return 0;
+    }
+
+    let scaled = u64::from(freq_hz) * u64::from(TONE_SCALE_PERCENT) / 100;
+    scaled.clamp(u64::from(TONE_MIN_FREQUENCY), u64::from(TONE_MAX_FREQUENCY)) as u32
+}
+
+// Wait out one half-period, given a clock returning microseconds elapsed
+fn wait_half_period(half_period_us: u64, elapsed_us: &impl Fn() -> u64) {
+    if !should_spin(half_period_us, MIN_SLEEP_THRESHOLD_US) {
+        // For longer periods, sleep is efficient enough
+        thread::sleep(Duration::from_micros(half_period_us));
+        return;
+    }
+
+    // For shorter periods, use a spin loop for better precision
+    let target = elapsed_us() + half_period_us;
+    while elapsed_us() < target {
+        // Busy wait (spin)
+    }
+}
+
+// Whether a button-triggered sound may play now, starting a new cooldown
+// if so and logging the skip if not
+fn manual_trigger_allowed(last_trigger: &mut Option<Instant>, what: &str) -> bool {
+    if let Some(t) = *last_trigger {
+        if t.elapsed().as_secs() < MANUAL_TRIGGER_COOLDOWN_SECS {
+            log::info!("Skipping {}, button pressed again too soon", what);
+            return false;
+        }
+    }
+
+    *last_trigger = Some(Instant::now());
+    true
+}
+
+// Beep the last octet of the station IP address in Morse
+fn announce_ip(wifi: &BlockingWifi<EspWifi<'_>>, buzzer_tx: &BuzzerChannel) {
+    if !wifi_is_connected(wifi) {
+        log::warn!("No IP address to announce, WiFi is not connected");
+        return;
+    }
+
+    match wifi.wifi().sta_netif().get_ip_info() {
+        Ok(ip_info) => {
+            let octet = ip_info.ip.octets()[3];
+            log::info!("Announcing IP {} as '{}' in Morse", ip_info.ip, octet);
+            send_to_buzzer(
+                buzzer_tx,
+                BuzzerMessage::PlayMorse {
+                    text: octet.to_string(),
+                },
+                "IP announcement",
+            );
+        }
+        Err(e) => log::warn!("Failed to read IP address: {:?}", e),
+    }
+}
+
+// Sound the current local time in the configured encoding
+fn announce_time(buzzer_tx: &BuzzerChannel) {
+    let Some(now) = alarm_clock_secs().filter(|&now| now >= MIN_VALID_EPOCH_SECS) else {
+        log::warn!("No valid time to announce");
+        return;
+    };
+
+    let now_with_tz = now + UTC_OFFSET_SECS;
+    let hours = (now_with_tz / 3600) % 24;
+    let mins = (now_with_tz / 60) % 60;
+    log::info!("Announcing the time {}:{:02}", hours, mins);
+
+    match TIME_ANNOUNCE_ENCODING {
+        TimeAnnounceEncoding::Morse => send_to_buzzer(
+            buzzer_tx,
+            BuzzerMessage::PlayMorse {
+                text: format!("{} {:02}", hours, mins),
+            },
+            "time announcement",
+        ),
+        TimeAnnounceEncoding::Repeater => {
+            let hour_beeps = match hours % 12 {
+                0 => 12,
+                h => h,
+            };
+            let groups = [
+                (REPEATER_HOUR_MELODY_NAME, hour_beeps),
+                (REPEATER_QUARTER_MELODY_NAME, mins / 15),
+                (REPEATER_MINUTE_MELODY_NAME, mins % 15),
+            ];
+            for (name, count) in groups {
+                if count > 0 {
+                    send_to_buzzer(
+                        buzzer_tx,
+                        BuzzerMessage::PlayMelody {
+                            notes: configured_melody(name),
+                            repeat_count: count as u8,
+                            growth: None,
+                        },
+                        "time announcement",
+                    );
+                }
+            }
+        }
+    }
+}
+
 // Check if WiFi is still connected
 fn wifi_is_connected<'a>(wifi: &BlockingWifi<EspWifi<'a>>) -> bool {
     match wifi.wifi().is_connected() {
@@ -284,45 +2680,454 @@ fn wifi_is_connected<'a>(wifi: &BlockingWifi<EspWifi<'a>>) -> bool {
     }
 }
 
-// Connect to WiFi network
-fn connect_wifi(
+// Bump the boot counter kept in NVS, written once per boot to limit wear
+fn increment_boot_count(nvs: EspDefaultNvsPartition) -> Result<u32> {
+    let mut storage = EspNvs::new(nvs, "alarm", true).context("Failed to open NVS namespace")?;
+    let count = storage.get_u32("boot_count")?.unwrap_or(0).wrapping_add(1);
+    storage.set_u32("boot_count", count)?;
+    Ok(count)
+}
+
+// Set the demo flag in NVS, returning whether it was clear, i.e. whether
+// the demo should play on this boot
+fn claim_demo_run(nvs: EspDefaultNvsPartition) -> Result<bool> {
+    let mut storage = EspNvs::new(nvs, "alarm", true).context("Failed to open NVS namespace")?;
+    if storage.get_u8("demo_done")?.is_some() {
+        return Ok(false);
+    }
+    storage.set_u8("demo_done", 1)?;
+    Ok(true)
+}
+
+// Uptime as days, hours and minutes, e.g. "2d 03h 15m"
+fn format_uptime(uptime: Duration) -> String {
+    let mins = uptime.as_secs() / 60;
+    format!(
+        "{}d {:02}h {:02}m",
+        mins / 1440,
+        (mins / 60) % 24,
+        mins % 60
+    )
+}
+
+// Configured device name, or "esp32-alarm-" plus the last three MAC bytes
+fn device_name() -> String {
+    if let Some(name) = DEVICE_NAME {
+        return name.to_string();
+    }
+
+    let mut mac = [0u8; 6];
+    unsafe {
+        esp_idf_svc::sys::esp_read_mac(
+            mac.as_mut_ptr(),
+            esp_idf_svc::sys::esp_mac_type_t_ESP_MAC_WIFI_STA,
+        );
+    }
+    format!("esp32-alarm-{:02x}{:02x}{:02x}", mac[3], mac[4], mac[5])
+}
+
+// Configure the ADC channel used to measure the battery voltage.
+// Line fitting calibration makes readings come back in millivolts.
+fn start_battery_monitor(adc: ADC1, pin: Gpio34) -> Result<BatteryChannel> {
+    let adc = AdcDriver::new(adc).context("Failed to initialize ADC1")?;
+    let config = AdcChannelConfig {
+        attenuation: attenuation::DB_11,
+        calibration: Calibration::Line,
+        ..Default::default()
+    };
+    AdcChannelDriver::new(adc, pin, &config).context("Failed to initialize battery ADC channel")
+}
+
+// Read the battery voltage in millivolts, scaled up by the divider ratio
+fn read_battery_mv(channel: &mut BatteryChannel) -> Result<u32> {
+    let pin_mv = channel.read().context("ADC read failed")?;
+    Ok(u32::from(pin_mv) * BATTERY_DIVIDER_RATIO)
+}
+
+// Signal strength of the current access point in dBm
+fn wifi_rssi() -> Option<i8> {
+    let mut ap_info: esp_idf_svc::sys::wifi_ap_record_t = Default::default();
+    esp!(unsafe { esp_idf_svc::sys::esp_wifi_sta_get_ap_info(&mut ap_info) }).ok()?;
+    Some(ap_info.rssi)
+}
+
+// Create and start the WiFi driver in station mode
+fn start_wifi(
     modem: impl Peripheral<P = hal::modem::Modem> + 'static,
     sysloop: EspSystemEventLoop,
+    nvs: EspDefaultNvsPartition,
     ssid: &str,
     password: &str,
+    hostname: &str,
 ) -> Result<BlockingWifi<EspWifi<'static>>> {
-    let nvs = EspDefaultNvsPartition::take()?;
-
     // Create WiFi driver with the network interface
-    let wifi = EspWifi::new(modem, sysloop.clone(), Some(nvs))?;
+    let mut wifi =
+        EspWifi::new(modem, sysloop.clone(), Some(nvs)).context("Failed to create WiFi driver")?;
+    wifi.sta_netif_mut()
+        .set_hostname(hostname)
+        .context("Failed to set hostname")?;
     let mut wifi = BlockingWifi::wrap(wifi, sysloop)?;
 
     // Create WiFi configuration
-    let wifi_configuration = Configuration::Client(ClientConfiguration {
+    let client_configuration = ClientConfiguration {
         ssid: heapless::String::try_from(ssid).unwrap_or_default(),
         password: heapless::String::try_from(password).unwrap_or_default(),
         ..Default::default()
-    });
+    };
+    let wifi_configuration = match softap_configuration(hostname) {
+        Some(ap_configuration) => {
+            log::info!("Starting soft AP '{}' alongside the station", hostname);
+            Configuration::Mixed(client_configuration, ap_configuration)
+        }
+        None => Configuration::Client(client_configuration),
+    };
 
-    wifi.set_configuration(&wifi_configuration)?;
-    wifi.start()?;
+    wifi.set_configuration(&wifi_configuration)
+        .context("Failed to apply WiFi configuration")?;
+    wifi.start().context("Failed to start WiFi")?;
 
+    Ok(wifi)
+}
+
+// Soft AP settings when it is enabled and has a usable password
+fn softap_configuration(ssid: &str) -> Option<AccessPointConfiguration> {
+    if !SOFTAP_ENABLED {
+        return None;
+    }
+
+    match SOFTAP_PASSWORD {
+        Some(password) if password.len() >= 8 => Some(AccessPointConfiguration {
+            ssid: heapless::String::try_from(ssid).unwrap_or_default(),
+            password: heapless::String::try_from(password).unwrap_or_default(),
+            auth_method: AuthMethod::WPA2Personal,
+            ..Default::default()
+        }),
+        _ => {
+            log::warn!("Soft AP disabled, SOFTAP_PASSWORD must be at least 8 characters");
+            None
+        }
+    }
+}
+
+// Connect to the configured WiFi network and wait for an IP address
+fn connect_wifi(wifi: &mut BlockingWifi<EspWifi<'static>>) -> Result<()> {
     log::info!("WiFi started, connecting...");
 
+    // A rejected country code leaves the driver's default channel plan in place
+    match set_wifi_country(WIFI_COUNTRY_CODE) {
+        Ok(()) => log::info!("WiFi country set to {}", WIFI_COUNTRY_CODE),
+        Err(e) => log::warn!("Failed to set WiFi country {}: {:?}", WIFI_COUNTRY_CODE, e),
+    }
+
     wifi.connect()?;
 
-    log::info!("Waiting for DHCP lease...");
-    wifi.wait_netif_up()?;
+    // Retry a slow DHCP server a few times rather than giving up on the first timeout
+    for attempt in 1..=DHCP_BOOT_ATTEMPTS {
+        log::info!(
+            "Waiting for DHCP lease (attempt {}/{})...",
+            attempt,
+            DHCP_BOOT_ATTEMPTS
+        );
+        match wifi.wait_netif_up() {
+            Ok(()) => {
+                let ip_info = wifi.wifi().sta_netif().get_ip_info()?;
+                log::info!("WiFi connected, IP: {}", ip_info.ip);
+                return Ok(());
+            }
+            Err(e) => {
+                log::warn!("No DHCP lease yet: {:?}", e);
+                thread::sleep(Duration::from_millis(DHCP_RETRY_DELAY_MS));
+            }
+        }
+    }
+
+    anyhow::bail!(
+        "WiFi associated but no DHCP lease after {} attempts",
+        DHCP_BOOT_ATTEMPTS
+    )
+}
+
+// Apply the regulatory country, which decides the usable WiFi channels
+fn set_wifi_country(code: &str) -> Result<()> {
+    let code = std::ffi::CString::new(code).context("Invalid country code")?;
+    esp!(unsafe { esp_idf_svc::sys::esp_wifi_set_country_code(code.as_ptr(), false) })?;
+    Ok(())
+}
 
-    let ip_info = wifi.wifi().sta_netif().get_ip_info()?;
-    log::info!("WiFi connected, IP: {}", ip_info.ip);
+// Seed the system clock from the firmware commit time if it holds no valid time
+fn seed_clock_from_build_time() {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    if now >= MIN_VALID_EPOCH_SECS {
+        return;
+    }
 
-    Ok(wifi)
+    let build_secs: u64 = BUILD_EPOCH_SECS.parse().unwrap_or(0);
+    if set_system_clock(build_secs) {
+        log::info!("Clock seeded from firmware commit time ({})", build_secs);
+    } else {
+        log::error!("Failed to seed clock from firmware commit time");
+    }
+}
+
+// Set the system clock to the given seconds since the epoch
+fn set_system_clock(secs: u64) -> bool {
+    let tv = esp_idf_svc::sys::timeval {
+        tv_sec: secs as _,
+        tv_usec: 0,
+    };
+    unsafe { esp_idf_svc::sys::settimeofday(&tv, std::ptr::null()) == 0 }
+}
+
+// Open the I2C bus to the DS3231 and seed the system clock from it
+fn start_ds3231(i2c: I2C0, sda: Gpio21, scl: Gpio22) -> Result<I2cDriver<'static>> {
+    let config = I2cConfig::new().baudrate(100.kHz().into());
+    let mut rtc = I2cDriver::new(i2c, sda, scl, &config).context("Failed to initialize I2C")?;
+
+    match ds3231_read_secs(&mut rtc).context("Failed to read DS3231")? {
+        Some(secs) if secs >= MIN_VALID_EPOCH_SECS => {
+            if set_system_clock(secs) {
+                log::info!("Clock seeded from DS3231 ({})", secs);
+            } else {
+                log::error!("Failed to seed clock from DS3231");
+            }
+        }
+        _ => log::warn!("DS3231 holds no valid time yet"),
+    }
+
+    Ok(rtc)
+}
+
+// Read the DS3231 time (kept in UTC) as seconds since the epoch, or None if
+// its oscillator has stopped since the time was last set or its registers
+// hold an impossible time
+fn ds3231_read_secs(rtc: &mut I2cDriver) -> Result<Option<u64>> {
+    let mut status = [0u8; 1];
+    rtc.write_read(DS3231_ADDRESS, &[0x0f], &mut status, BLOCK)?;
+    if status[0] & 0x80 != 0 {
+        return Ok(None);
+    }
+
+    let mut regs = [0u8; 7];
+    rtc.write_read(DS3231_ADDRESS, &[0x00], &mut regs, BLOCK)?;
+
+    let secs = u64::from(from_bcd(regs[0] & 0x7f));
+    let mins = u64::from(from_bcd(regs[1] & 0x7f));
+    let hours = if regs[2] & 0x40 != 0 {
+        // 12-hour mode, bit 5 is PM
+        u64::from(from_bcd(regs[2] & 0x1f) % 12) + if regs[2] & 0x20 != 0 { 12 } else { 0 }
+    } else {
+        u64::from(from_bcd(regs[2] & 0x3f))
+    };
+    let day = u32::from(from_bcd(regs[4] & 0x3f));
+    let month = u32::from(from_bcd(regs[5] & 0x1f));
+    let year = 2000 + u32::from(from_bcd(regs[6]));
+
+    // A glitched read or corrupt registers can decode out of range; treat
+    // that like a stopped oscillator rather than seed a nonsense time
+    if secs >= 60
+        || mins >= 60
+        || hours >= 24
+        || !(1..=31).contains(&day)
+        || !(1..=12).contains(&month)
+    {
+        return Ok(None);
+    }
+
+    let days = days_from_civil(year, month, day);
+    Ok(Some(days * 86_400 + hours * 3600 + mins * 60 + secs))
+}
+
+// Set the DS3231 to the given UTC time and clear its oscillator-stop flag
+fn ds3231_write_secs(rtc: &mut I2cDriver, secs: u64) -> Result<()> {
+    let days = secs / 86_400;
+    let time_of_day = secs % 86_400;
+    let (year, month, day) = civil_from_days(days);
+    let weekday = (days + 3) % 7 + 1; // 1 = Monday, 1970-01-01 was a Thursday
+
+    rtc.write(
+        DS3231_ADDRESS,
+        &[
+            0x00,
+            to_bcd((time_of_day % 60) as u8),
+            to_bcd((time_of_day / 60 % 60) as u8),
+            to_bcd((time_of_day / 3600) as u8), // 24-hour mode
+            weekday as u8,
+            to_bcd(day as u8),
+            to_bcd(month as u8),
+            to_bcd((year % 100) as u8),
+        ],
+        BLOCK,
+    )?;
+
+    let mut status = [0u8; 1];
+    rtc.write_read(DS3231_ADDRESS, &[0x0f], &mut status, BLOCK)?;
+    rtc.write(DS3231_ADDRESS, &[0x0f, status[0] & !0x80], BLOCK)?;
+    Ok(())
+}
+
+fn from_bcd(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0f)
+}
+
+fn to_bcd(value: u8) -> u8 {
+    ((value / 10) << 4) | (value % 10)
+}
+
+// Wait for the first SNTP sync, returning whether the clock is usable.
+// Gives up after BOOT_SYNC_TIMEOUT_SECS; the main loop then holds alarms
+// back until the clock becomes valid.
+fn wait_for_boot_sync(sntp: &EspSntp) -> bool {
+    let start = Instant::now();
+
+    while sntp.get_sync_status() != SyncStatus::Completed {
+        if start.elapsed().as_secs() >= BOOT_SYNC_TIMEOUT_SECS {
+            log::warn!("No SNTP sync after {} s", BOOT_SYNC_TIMEOUT_SECS);
+            return match BOOT_SYNC_POLICY {
+                BootSyncPolicy::Completed => false,
+                BootSyncPolicy::PlausibleAfterTimeout => {
+                    let now = SystemTime::now()
+                        .duration_since(SystemTime::UNIX_EPOCH)
+                        .map(|d| d.as_secs())
+                        .unwrap_or(0);
+                    let plausible = now >= MIN_VALID_EPOCH_SECS;
+                    if plausible {
+                        log::info!("Accepting the current clock without a completed sync");
+                    }
+                    plausible
+                }
+            };
+        }
+        thread::sleep(Duration::from_millis(500));
+    }
+
+    log::info!("Initial time sync complete");
+    true
+}
+
+// Start the mDNS responder and advertise the device hostname
+fn start_mdns(hostname: &str) -> Result<EspMdns> {
+    let mut mdns = EspMdns::take()?;
+    mdns.set_instance_name("ESP32 Alarm Clock")?;
+    announce_mdns(&mut mdns, hostname)?;
+    Ok(mdns)
+}
+
+// (Re-)announce the hostname, logging the one the responder registered,
+// which carries a suffix if it had to resolve a name clash
+fn announce_mdns(mdns: &mut EspMdns, hostname: &str) -> Result<()> {
+    mdns.set_hostname(hostname)?;
+    match mdns_hostname() {
+        Some(registered) => log::info!("mDNS hostname: {}.local", registered),
+        None => log::info!("mDNS hostname: {}.local (requested)", hostname),
+    }
+    Ok(())
+}
+
+// Hostname currently held by the mDNS responder
+fn mdns_hostname() -> Option<String> {
+    let mut buf = [0 as std::ffi::c_char; esp_idf_svc::sys::MDNS_NAME_BUF_LEN as usize];
+    esp!(unsafe { esp_idf_svc::sys::mdns_hostname_get(buf.as_mut_ptr()) }).ok()?;
+    let hostname = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) };
+    Some(hostname.to_string_lossy().into_owned())
+}
+
+// Ask SNTP to sync immediately instead of at its next scheduled poll
+fn request_sntp_resync() {
+    if unsafe { esp_idf_svc::sys::esp_sntp_restart() } {
+        log::info!("SNTP resync requested");
+    } else {
+        log::warn!("Failed to restart SNTP for resync");
+    }
+}
+
+// When the last SNTP sync arrived, if any has
+fn last_sntp_sync() -> Option<Instant> {
+    LAST_SNTP_SYNC.lock().ok().and_then(|last| *last)
+}
+
+// Add an entry to the SNTP sync history
+fn record_sync(offset_ms: Option<i64>, synced: bool) {
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    if let Ok(mut history) = SYNC_HISTORY.lock() {
+        history.write(SyncRecord {
+            timestamp,
+            offset_ms,
+            synced,
+        });
+    }
+}
+
+// Log the SNTP sync history oldest first, one local time and outcome per entry
+fn log_sync_history() {
+    let Ok(history) = SYNC_HISTORY.lock() else {
+        return;
+    };
+    if history.is_empty() {
+        log::info!("SNTP history: no syncs yet");
+        return;
+    }
+
+    let entries: Vec<String> = history
+        .oldest_ordered()
+        .map(|record| {
+            let local = record.timestamp + UTC_OFFSET_SECS;
+            let time = format!("{:02}:{:02}", (local / 3600) % 24, (local / 60) % 60);
+            match (record.synced, record.offset_ms) {
+                (false, _) => format!("{} missed", time),
+                (true, Some(offset_ms)) => format!("{} {:+} ms", time, offset_ms),
+                (true, None) => format!("{} first", time),
+            }
+        })
+        .collect();
+    log::info!("SNTP history: {}", entries.join(", "));
 }
 
 // Setup SNTP service for time synchronization
 fn setup_sntp() -> Result<EspSntp<'static>> {
-    let sntp = EspSntp::new_default()?;
+    // Poll at NTP_SYNC_INTERVAL rather than the sdkconfig default
+    unsafe { esp_idf_svc::sys::sntp_set_sync_interval((NTP_SYNC_INTERVAL * 1000) as u32) };
+
+    // Wall-clock time and monotonic instant of the previous sync
+    let mut last_sync: Option<(Instant, Duration)> = None;
+
+    let sntp = EspSntp::new_with_callback(&SntpConf::default(), move |synced| {
+        let now = Instant::now();
+        SNTP_SYNC_COUNT.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut last) = LAST_SNTP_SYNC.lock() {
+            *last = Some(now);
+        }
+
+        match last_sync {
+            Some((at, wall)) => {
+                // Compare the synced time to what the local clock should have read
+                let expected = wall + now.duration_since(at);
+                let offset_ms = synced.as_millis() as i64 - expected.as_millis() as i64;
+
+                if offset_ms.unsigned_abs() > SNTP_OFFSET_WARN_MS {
+                    log::warn!("SNTP sync adjusted clock by {} ms", offset_ms);
+                } else {
+                    log::info!("SNTP sync adjusted clock by {} ms", offset_ms);
+                }
+                emit_event(Event::TimeSynced {
+                    offset_ms: Some(offset_ms),
+                });
+                record_sync(Some(offset_ms), true);
+            }
+            None => {
+                log::info!("Initial SNTP sync received");
+                emit_event(Event::TimeSynced { offset_ms: None });
+                record_sync(None, true);
+            }
+        }
+
+        last_sync = Some((now, synced));
+    })?;
     log::info!("SNTP initialized, waiting for time sync...");
     Ok(sntp)
 }